@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArgKind {
+    Positional,
+    Option,
+    Flag,
+}
+
+/// Definition of a command line argument
+#[derive(Debug, Clone)]
+pub struct Arg {
+    name: &'static str,
+    kind: ArgKind,
+    short: Option<char>,
+    help: &'static str,
+    value_name: &'static str,
+    default: Option<&'static str>,
+    required: bool,
+    multiple: bool,
+}
+
+impl Arg {
+    fn new(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            short: None,
+            help: "",
+            value_name: name,
+            default: None,
+            required: kind == ArgKind::Positional,
+            multiple: false,
+        }
+    }
+
+    /// Required positional argument `<name>`
+    pub fn positional(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Positional)
+    }
+
+    /// Option with value `--name <value>`
+    pub fn option(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Option)
+    }
+
+    /// Flag without value `--name`
+    pub fn flag(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Flag)
+    }
+
+    /// Sets short form `-c` of option or flag
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    pub fn help(mut self, help: &'static str) -> Self {
+        self.help = help;
+        self
+    }
+
+    /// Sets name of option value shown in usage text
+    pub fn value_name(mut self, value_name: &'static str) -> Self {
+        self.value_name = value_name;
+        self
+    }
+
+    /// Sets value used when option is not given
+    pub fn default(mut self, default: &'static str) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    /// Makes positional argument optional
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    /// Positional argument takes all remaining values,
+    /// option can be given several times
+    pub fn multiple(mut self) -> Self {
+        self.multiple = true;
+        self
+    }
+
+    fn usage_name(&self) -> String {
+        let dots = if self.multiple { "..." } else { "" };
+        match self.kind {
+            ArgKind::Positional if self.required => format!("<{}>{dots}", self.value_name),
+            ArgKind::Positional => format!("[{}]{dots}", self.value_name),
+            ArgKind::Option => {
+                let short = self.short.map(|x| format!("-{x}, ")).unwrap_or_default();
+                format!("{short}--{} <{}>", self.name, self.value_name)
+            },
+            ArgKind::Flag => {
+                let short = self.short.map(|x| format!("-{x}, ")).unwrap_or_default();
+                format!("{short}--{}", self.name)
+            },
+        }
+    }
+}
+
+/// Command with arguments and subcommands
+#[derive(Debug, Clone)]
+pub struct Command {
+    name: &'static str,
+    about: &'static str,
+    args: Vec<Arg>,
+    subcommands: Vec<Command>,
+}
+
+impl Command {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            about: "",
+            args: Vec::new(),
+            subcommands: Vec::new(),
+        }
+    }
+
+    /// Sets description shown in usage text
+    pub fn about(mut self, about: &'static str) -> Self {
+        self.about = about;
+        self
+    }
+
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn subcommand(mut self, command: Command) -> Self {
+        self.subcommands.push(command);
+        self
+    }
+
+    /// Parses arguments without program name
+    pub fn parse<I: IntoIterator<Item = String>>(&self, args: I) -> Result<Matches, ParseError> {
+        let args: Vec<String> = args.into_iter().collect();
+        self.parse_args(&args, self.name)
+    }
+
+    /// Parses arguments of the current process
+    pub fn parse_env(&self) -> Result<Matches, ParseError> {
+        self.parse(std::env::args().skip(1))
+    }
+
+    /// Returns usage text of the command, `path` is the command line
+    /// preceding command arguments, e.g. `tokenizer_cli fill`
+    pub fn usage(&self, path: &str) -> String {
+        let mut result = format!("usage: {path}");
+        if !self.subcommands.is_empty() {
+            result.push_str(" <command>");
+        } else {
+            if self.args.iter().any(|x| x.kind != ArgKind::Positional) {
+                result.push_str(" [options]");
+            }
+            for arg in self.args.iter().filter(|x| x.kind == ArgKind::Positional) {
+                result.push(' ');
+                result.push_str(&arg.usage_name());
+            }
+        }
+        result.push('\n');
+
+        if !self.about.is_empty() {
+            result.push_str(&format!("\n{}\n", self.about));
+        }
+
+        if !self.subcommands.is_empty() {
+            let rows: Vec<(String, String)> = self.subcommands.iter()
+                .map(|x| (x.name.to_string(), x.about.to_string()))
+                .collect();
+            result.push_str("\nCommands:\n");
+            result.push_str(&format_rows(&rows));
+            result.push_str(&format!("\nRun '{path} <command> --help' for command options.\n"));
+            return result;
+        }
+
+        let positionals: Vec<(String, String)> = self.args.iter()
+            .filter(|x| x.kind == ArgKind::Positional)
+            .map(|x| (x.usage_name(), x.help.to_string()))
+            .collect();
+        if !positionals.is_empty() {
+            result.push_str("\nArguments:\n");
+            result.push_str(&format_rows(&positionals));
+        }
+
+        let mut options: Vec<(String, String)> = self.args.iter()
+            .filter(|x| x.kind != ArgKind::Positional)
+            .map(|x| {
+                let help = match x.default {
+                    Some(default) => format!("{} [default: {default}]", x.help),
+                    None => x.help.to_string(),
+                };
+                (x.usage_name(), help)
+            })
+            .collect();
+        options.push(("-h, --help".to_string(), "Print help".to_string()));
+        result.push_str("\nOptions:\n");
+        result.push_str(&format_rows(&options));
+
+        result
+    }
+
+    fn find_option(&self, name: &str) -> Option<&Arg> {
+        self.args.iter().find(|x| x.kind != ArgKind::Positional && x.name == name)
+    }
+
+    fn find_short(&self, short: char) -> Option<&Arg> {
+        self.args.iter().find(|x| x.kind != ArgKind::Positional && x.short == Some(short))
+    }
+
+    fn parse_args(&self, args: &[String], path: &str) -> Result<Matches, ParseError> {
+        let usage = self.usage(path);
+        let invalid = |message: String| ParseError::Invalid {
+            message,
+            usage: usage.clone(),
+        };
+
+        let mut matches = Matches {
+            values: HashMap::new(),
+            subcommand: None,
+            usage: usage.clone(),
+        };
+        let mut positionals: Vec<String> = Vec::new();
+        let mut only_positionals = false;
+
+        let mut i = 0;
+        while i < args.len() {
+            let arg = &args[i];
+            i += 1;
+
+            if only_positionals || arg == "-" || !arg.starts_with('-') {
+                if !self.subcommands.is_empty() {
+                    let Some(command) = self.subcommands.iter().find(|x| x.name == arg) else {
+                        return Err(invalid(format!("unknown command '{arg}'")));
+                    };
+                    let sub_matches = command.parse_args(&args[i..], &format!("{path} {}", command.name))?;
+                    matches.subcommand = Some((command.name, Box::new(sub_matches)));
+                    break;
+                }
+                positionals.push(arg.clone());
+                continue;
+            }
+
+            if arg == "--" {
+                only_positionals = true;
+                continue;
+            }
+            if arg == "--help" || arg == "-h" {
+                return Err(ParseError::Help(usage));
+            }
+
+            // --name=value, --name, -c value, -cvalue
+            let (option, inline_value) = if let Some(long) = arg.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                (self.find_option(name), value)
+            } else {
+                let mut chars = arg[1..].chars();
+                let short = chars.next().unwrap();
+                let rest: String = chars.collect();
+                (self.find_short(short), (!rest.is_empty()).then_some(rest))
+            };
+            let Some(option) = option else {
+                return Err(invalid(format!("unknown option '{arg}'")));
+            };
+
+            let value = match option.kind {
+                ArgKind::Flag => {
+                    if inline_value.is_some() {
+                        return Err(invalid(format!("flag '--{}' doesn't take a value", option.name)));
+                    }
+                    String::new()
+                },
+                _ => match inline_value {
+                    Some(value) => value,
+                    None => {
+                        let Some(value) = args.get(i) else {
+                            return Err(invalid(format!("option '--{}' requires a value", option.name)));
+                        };
+                        i += 1;
+                        value.clone()
+                    },
+                },
+            };
+
+            let values = matches.values.entry(option.name).or_default();
+            if !option.multiple {
+                values.clear();
+            }
+            values.push(value);
+        }
+
+        if !self.subcommands.is_empty() && matches.subcommand.is_none() {
+            return Err(invalid("missing command".to_string()));
+        }
+
+        let mut positionals = positionals.into_iter();
+        for arg in self.args.iter().filter(|x| x.kind == ArgKind::Positional) {
+            let values: Vec<String> = if arg.multiple {
+                positionals.by_ref().collect()
+            } else {
+                positionals.next().into_iter().collect()
+            };
+            if values.is_empty() {
+                if arg.required {
+                    return Err(invalid(format!("missing argument <{}>", arg.value_name)));
+                }
+                continue;
+            }
+            matches.values.insert(arg.name, values);
+        }
+        if let Some(arg) = positionals.next() {
+            return Err(invalid(format!("unexpected argument '{arg}'")));
+        }
+
+        for arg in self.args.iter() {
+            if let Some(default) = arg.default {
+                matches.values.entry(arg.name).or_insert_with(|| vec![default.to_string()]);
+            }
+        }
+
+        Ok(matches)
+    }
+}
+
+fn format_rows(rows: &[(String, String)]) -> String {
+    let width = rows.iter().map(|x| x.0.chars().count()).max().unwrap_or(0);
+    let mut result = String::new();
+    for (name, help) in rows {
+        let line = format!("  {name:<width$}  {help}");
+        result.push_str(line.trim_end());
+        result.push('\n');
+    }
+    result
+}
+
+/// Parsed arguments
+#[derive(Debug, Clone)]
+pub struct Matches {
+    values: HashMap<&'static str, Vec<String>>,
+    subcommand: Option<(&'static str, Box<Matches>)>,
+    usage: String,
+}
+
+impl Matches {
+    /// Returns name and arguments of given subcommand
+    pub fn subcommand(&self) -> Option<(&str, &Matches)> {
+        self.subcommand.as_ref().map(|(name, matches)| (*name, matches.as_ref()))
+    }
+
+    /// Returns `true` if flag is given
+    pub fn flag(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Returns raw value of argument
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.values.get(name).and_then(|x| x.last()).map(|x| x.as_str())
+    }
+
+    /// Parses value of argument, if argument is not given returns `None`
+    pub fn get_opt<T>(&self, name: &str) -> Result<Option<T>, ParseError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get_str(name).map(|x| self.parse_value(name, x)).transpose()
+    }
+
+    /// Parses value of argument which is required or has default value
+    pub fn get<T>(&self, name: &str) -> Result<T, ParseError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get_opt(name)?.ok_or_else(|| ParseError::Invalid {
+            message: format!("missing argument '{name}'"),
+            usage: self.usage.clone(),
+        })
+    }
+
+    /// Parses all values of argument
+    pub fn get_many<T>(&self, name: &str) -> Result<Vec<T>, ParseError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.values.get(name)
+            .map(|values| values.iter().map(|x| self.parse_value(name, x)).collect())
+            .unwrap_or(Ok(Vec::new()))
+    }
+
+    /// Returns usage error of the command, e.g. for conflicting arguments
+    pub fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError::Invalid {
+            message: message.into(),
+            usage: self.usage.clone(),
+        }
+    }
+
+    fn parse_value<T>(&self, name: &str, value: &str) -> Result<T, ParseError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        value.parse().map_err(|e| ParseError::Invalid {
+            message: format!("invalid value '{value}' for '{name}': {e}"),
+            usage: self.usage.clone(),
+        })
+    }
+}
+
+/// Error of argument parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// Help was requested, contains usage text
+    Help(String),
+
+    /// Arguments are invalid
+    Invalid {
+        message: String,
+        usage: String,
+    },
+}
+
+impl ParseError {
+    /// Prints help to stdout or error to stderr and exits the process
+    pub fn exit(&self) -> ! {
+        match self {
+            ParseError::Help(usage) => {
+                print!("{usage}");
+                std::process::exit(0);
+            },
+            ParseError::Invalid { .. } => {
+                eprint!("{self}");
+                std::process::exit(2);
+            },
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Help(usage) => write!(f, "{usage}"),
+            ParseError::Invalid { message, usage } => write!(f, "error: {message}\n\n{usage}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn command() -> Command {
+        Command::new("prog")
+            .about("Test program")
+            .subcommand(Command::new("fill")
+                .about("Fills vocabulary")
+                .arg(Arg::positional("vocab").help("Vocabulary file"))
+                .arg(Arg::positional("corpus").multiple().optional().help("Corpus files"))
+                .arg(Arg::option("size").short('s').value_name("n").default("100").help("Vocabulary size"))
+                .arg(Arg::option("temperature").short('t').help("Sampling temperature"))
+                .arg(Arg::flag("verbose").short('v').help("Print progress")))
+            .subcommand(Command::new("show")
+                .arg(Arg::positional("vocab").optional()))
+    }
+
+    #[test]
+    fn parse() {
+        let matches = command().parse(args(&["fill", "-s", "5", "v.vcb", "a.txt", "--verbose", "b.txt"])).unwrap();
+        let (name, matches) = matches.subcommand().unwrap();
+        assert_eq!(name, "fill");
+        assert_eq!(matches.get::<usize>("size"), Ok(5));
+        assert_eq!(matches.get::<String>("vocab"), Ok("v.vcb".to_string()));
+        assert_eq!(matches.get_many::<String>("corpus"), Ok(args(&["a.txt", "b.txt"])));
+        assert_eq!(matches.get_opt::<f64>("temperature"), Ok(None));
+        assert!(matches.flag("verbose"));
+
+        let matches = command().parse(args(&["fill", "--size=7", "-t2.5", "v.vcb", "--", "-a.txt"])).unwrap();
+        let (_, matches) = matches.subcommand().unwrap();
+        assert_eq!(matches.get::<usize>("size"), Ok(7));
+        assert_eq!(matches.get_opt::<f64>("temperature"), Ok(Some(2.5)));
+        assert_eq!(matches.get_many::<String>("corpus"), Ok(args(&["-a.txt"])));
+        assert!(!matches.flag("verbose"));
+
+        let matches = command().parse(args(&["fill", "v.vcb"])).unwrap();
+        let (_, matches) = matches.subcommand().unwrap();
+        assert_eq!(matches.get::<usize>("size"), Ok(100));
+
+        let matches = command().parse(args(&["show"])).unwrap();
+        assert_eq!(matches.subcommand().unwrap().1.get_str("vocab"), None);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |x: &[&str]| match command().parse(args(x)) {
+            Err(ParseError::Invalid { message, .. }) => message,
+            x => panic!("unexpected result {x:?}"),
+        };
+
+        assert_eq!(error(&[]), "missing command");
+        assert_eq!(error(&["lol"]), "unknown command 'lol'");
+        assert_eq!(error(&["fill"]), "missing argument <vocab>");
+        assert_eq!(error(&["fill", "v.vcb", "--lol"]), "unknown option '--lol'");
+        assert_eq!(error(&["fill", "v.vcb", "--size"]), "option '--size' requires a value");
+        assert_eq!(error(&["fill", "v.vcb", "--verbose=1"]), "flag '--verbose' doesn't take a value");
+        assert_eq!(error(&["show", "a", "b"]), "unexpected argument 'b'");
+
+        let matches = command().parse(args(&["fill", "-s", "many", "v.vcb"])).unwrap();
+        let error = matches.subcommand().unwrap().1.get::<usize>("size").unwrap_err();
+        assert!(error.to_string().starts_with("error: invalid value 'many' for 'size': invalid digit"));
+        let error = matches.subcommand().unwrap().1.error("conflicting arguments");
+        assert!(error.to_string().starts_with("error: conflicting arguments\n\nusage: prog fill"));
+    }
+
+    #[test]
+    fn help() {
+        let Err(ParseError::Help(usage)) = command().parse(args(&["fill", "--help"])) else {
+            panic!("help expected");
+        };
+        let expected = [
+            "usage: prog fill [options] <vocab> [corpus]...",
+            "",
+            "Fills vocabulary",
+            "",
+            "Arguments:",
+            "  <vocab>      Vocabulary file",
+            "  [corpus]...  Corpus files",
+            "",
+            "Options:",
+            "  -s, --size <n>                   Vocabulary size [default: 100]",
+            "  -t, --temperature <temperature>  Sampling temperature",
+            "  -v, --verbose                    Print progress",
+            "  -h, --help                       Print help",
+        ];
+        assert_eq!(usage, expected.map(|x| x.to_string() + "\n").concat());
+
+        let Err(ParseError::Help(usage)) = command().parse(args(&["-h"])) else {
+            panic!("help expected");
+        };
+        assert!(usage.contains("Commands:\n  fill  Fills vocabulary\n  show\n"));
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use tokenizer::{tokenizers::TokenId, Fingerprint};
+
+pub const MAGIC: [u8; 4] = *b"SMDS";
+pub const VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 32;
+
+const FLAG_INDEX: u8 = 1;
+
+/// Type of stored token ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DType {
+    U16,
+    U32,
+}
+
+impl DType {
+    /// Returns size of one token in bytes
+    pub fn size(self) -> usize {
+        match self {
+            DType::U16 => 2,
+            DType::U32 => 4,
+        }
+    }
+
+    /// Returns smallest type able to store ids up to `max_id`
+    pub fn for_max_id(max_id: TokenId) -> Self {
+        if max_id <= u16::MAX as TokenId {
+            DType::U16
+        } else {
+            DType::U32
+        }
+    }
+
+    fn from_size(size: u8) -> Result<Self, DatasetError> {
+        match size {
+            2 => Ok(DType::U16),
+            4 => Ok(DType::U32),
+            x => Err(DatasetError::UnknownDType(x)),
+        }
+    }
+}
+
+impl fmt::Display for DType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DType::U16 => write!(f, "u16"),
+            DType::U32 => write!(f, "u32"),
+        }
+    }
+}
+
+impl std::str::FromStr for DType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "u16" => Ok(DType::U16),
+            "u32" => Ok(DType::U32),
+            x => Err(format!("unknown dtype {x}, expected u16 or u32")),
+        }
+    }
+}
+
+/// Fixed size header at the start of dataset file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub dtype: DType,
+    pub fingerprint: Fingerprint,
+    pub tokens: u64,
+    pub documents: u64,
+
+    /// `true` if document start offsets follow the token array
+    pub has_index: bool,
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[6] = self.dtype.size() as u8;
+        bytes[7] = if self.has_index { FLAG_INDEX } else { 0 };
+        bytes[8..16].copy_from_slice(&self.fingerprint.0.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.tokens.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.documents.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DatasetError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(DatasetError::Truncated);
+        }
+        if bytes[0..4] != MAGIC {
+            return Err(DatasetError::BadMagic);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != VERSION {
+            return Err(DatasetError::UnsupportedVersion(version));
+        }
+
+        let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        Ok(Self {
+            dtype: DType::from_size(bytes[6])?,
+            has_index: bytes[7] & FLAG_INDEX != 0,
+            fingerprint: Fingerprint(u64_at(8)),
+            tokens: u64_at(16),
+            documents: u64_at(24),
+        })
+    }
+
+    /// Returns expected size of the whole file
+    pub fn file_size(&self) -> Option<usize> {
+        let tokens = usize::try_from(self.tokens).ok()?.checked_mul(self.dtype.size())?;
+        let index = if self.has_index {
+            usize::try_from(self.documents).ok()?.checked_mul(8)?
+        } else {
+            0
+        };
+        HEADER_SIZE.checked_add(tokens)?.checked_add(index)
+    }
+}
+
+/// Error of reading or writing dataset
+#[derive(Debug)]
+pub enum DatasetError {
+    Io(std::io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnknownDType(u8),
+
+    /// File is shorter than its header claims
+    Truncated,
+
+    /// Token id doesn't fit into dataset's dtype
+    TokenOverflow {
+        token: TokenId,
+        dtype: DType,
+    },
+}
+
+impl fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasetError::Io(e) => write!(f, "{e}"),
+            DatasetError::BadMagic => write!(f, "not a dataset file"),
+            DatasetError::UnsupportedVersion(x) => write!(f, "unsupported dataset version {x}, expected {VERSION}"),
+            DatasetError::UnknownDType(x) => write!(f, "unknown token size {x}"),
+            DatasetError::Truncated => write!(f, "dataset file is truncated"),
+            DatasetError::TokenOverflow { token, dtype } => write!(f, "token {token} doesn't fit into {dtype}"),
+        }
+    }
+}
+
+impl std::error::Error for DatasetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DatasetError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for DatasetError {
+    fn from(value: std::io::Error) -> Self {
+        DatasetError::Io(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        Header {
+            dtype: DType::U32,
+            fingerprint: Fingerprint(0x0123_4567_89ab_cdef),
+            tokens: 5,
+            documents: 2,
+            has_index: true,
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bytes = header().to_bytes();
+        assert_eq!(&bytes[0..4], b"SMDS");
+        assert_eq!(Header::from_bytes(&bytes).unwrap(), header());
+        assert_eq!(header().file_size(), Some(HEADER_SIZE + 5 * 4 + 2 * 8));
+
+        let plain = Header { dtype: DType::U16, has_index: false, ..header() };
+        assert_eq!(Header::from_bytes(&plain.to_bytes()).unwrap(), plain);
+        assert_eq!(plain.file_size(), Some(HEADER_SIZE + 5 * 2));
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = header().to_bytes();
+        assert!(matches!(Header::from_bytes(&bytes[..HEADER_SIZE - 1]), Err(DatasetError::Truncated)));
+
+        let mut future = bytes;
+        future[4] = 2;
+        assert!(matches!(Header::from_bytes(&future), Err(DatasetError::UnsupportedVersion(2))));
+
+        let mut odd = bytes;
+        odd[6] = 3;
+        assert!(matches!(Header::from_bytes(&odd), Err(DatasetError::UnknownDType(3))));
+
+        let mut other = bytes;
+        other[0] = b'X';
+        assert!(matches!(Header::from_bytes(&other), Err(DatasetError::BadMagic)));
+    }
+}
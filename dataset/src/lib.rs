@@ -0,0 +1,24 @@
+//! Pre-tokenized dataset file.
+//!
+//! Layout, all numbers are little-endian:
+//!
+//! | bytes     | content                                           |
+//! |-----------|---------------------------------------------------|
+//! | 4         | magic `SMDS`                                      |
+//! | 2         | format version                                    |
+//! | 1         | [`DType`], size of one token in bytes              |
+//! | 1         | flags, bit 0 is set if document index is present  |
+//! | 8         | fingerprint of vocabulary used for tokenization   |
+//! | 8         | count of tokens                                   |
+//! | 8         | count of documents                                |
+//! | tokens × dtype | flat token array                             |
+//! | documents × 8  | start of every document in the token array, only with index |
+
+mod header;
+mod mmap;
+mod reader;
+mod writer;
+
+pub use header::*;
+pub use reader::*;
+pub use writer::*;
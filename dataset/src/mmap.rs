@@ -0,0 +1,136 @@
+use std::fs::File;
+
+/// Read-only content of a file, memory-mapped on 64-bit unix
+/// and read into memory on other platforms.
+///
+/// `mmap` is declared by hand with 64-bit `off_t`, which 32-bit targets
+/// don't always have, so they read the file too.
+pub struct MappedFile {
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    map: unix::Map,
+    #[cfg(not(all(unix, target_pointer_width = "64")))]
+    bytes: Vec<u8>,
+}
+
+impl MappedFile {
+    pub fn open(file: &File) -> Result<Self, std::io::Error> {
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        {
+            Ok(Self {
+                map: unix::Map::new(file)?,
+            })
+        }
+        #[cfg(not(all(unix, target_pointer_width = "64")))]
+        {
+            use std::io::Read;
+
+            let mut bytes = Vec::new();
+            (&*file).read_to_end(&mut bytes)?;
+            Ok(Self {
+                bytes,
+            })
+        }
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        {
+            self.map.as_slice()
+        }
+        #[cfg(not(all(unix, target_pointer_width = "64")))]
+        {
+            &self.bytes
+        }
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod unix {
+    use std::ffi::{c_int, c_void};
+    use std::fs::File;
+    use std::os::fd::AsRawFd;
+
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+    const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    pub struct Map {
+        ptr: *mut c_void,
+        len: usize,
+    }
+
+    // The mapping is read-only and owned by `Map`
+    unsafe impl Send for Map {}
+    unsafe impl Sync for Map {}
+
+    impl Map {
+        pub fn new(file: &File) -> Result<Self, std::io::Error> {
+            let len = usize::try_from(file.metadata()?.len())
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "file is too large to map"))?;
+            // Mapping of zero length is an error
+            if len == 0 {
+                return Ok(Self {
+                    ptr: std::ptr::null_mut(),
+                    len,
+                });
+            }
+
+            // SAFETY: arguments describe a fresh private read-only mapping of an open file
+            let ptr = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+            if ptr == MAP_FAILED {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(Self {
+                ptr,
+                len,
+            })
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            if self.len == 0 {
+                return &[];
+            }
+            // SAFETY: `ptr` points to `len` mapped bytes alive until drop
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Map {
+        fn drop(&mut self) {
+            if self.len != 0 {
+                // SAFETY: the mapping was created by `mmap` with the same length
+                unsafe {
+                    munmap(self.ptr, self.len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn open() {
+        let path = std::env::temp_dir().join(format!("dataset_mmap_{}", std::process::id()));
+
+        File::create(&path).unwrap();
+        let empty = MappedFile::open(&File::open(&path).unwrap()).unwrap();
+        assert_eq!(empty.as_ref(), b"");
+
+        File::create(&path).unwrap().write_all(b"tokens").unwrap();
+        let mapped = MappedFile::open(&File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mapped.as_ref(), b"tokens");
+    }
+}
@@ -0,0 +1,193 @@
+use std::io::Read;
+use std::ops::Range;
+use std::path::Path;
+
+use tokenizer::{tokenizers::TokenId, FingerprintMismatch, Vocabulary};
+
+use crate::{mmap::MappedFile, DType, DatasetError, Header, HEADER_SIZE};
+
+/// Random-access view of dataset bytes
+pub struct Dataset<B: AsRef<[u8]> = Vec<u8>> {
+    bytes: B,
+    header: Header,
+}
+
+impl Dataset<Vec<u8>> {
+    /// Reads the whole dataset into memory
+    pub fn read(input: &mut impl Read) -> Result<Self, DatasetError> {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        Self::from_bytes(bytes)
+    }
+}
+
+impl Dataset<MappedFile> {
+    /// Opens memory-mapped dataset file, tokens are read from disk on access
+    pub fn open(path: &Path) -> Result<Self, DatasetError> {
+        let file = std::fs::File::open(path)?;
+        Self::from_bytes(MappedFile::open(&file)?)
+    }
+}
+
+impl<B: AsRef<[u8]>> Dataset<B> {
+    pub fn from_bytes(bytes: B) -> Result<Self, DatasetError> {
+        let header = Header::from_bytes(bytes.as_ref())?;
+        match header.file_size() {
+            Some(size) if size <= bytes.as_ref().len() => Ok(Self {
+                bytes,
+                header,
+            }),
+            _ => Err(DatasetError::Truncated),
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Checks that the dataset was tokenized with given vocabulary
+    pub fn check_vocabulary(&self, vocab: &Vocabulary) -> Result<(), FingerprintMismatch> {
+        self.header.fingerprint.verify(vocab.fingerprint())
+    }
+
+    /// Returns count of tokens
+    pub fn len(&self) -> usize {
+        self.header.tokens as usize
+    }
+
+    /// Returns `true` if dataset has no tokens
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns token at given position
+    pub fn get(&self, index: usize) -> Option<TokenId> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let size = self.header.dtype.size();
+        let bytes = &self.bytes.as_ref()[HEADER_SIZE + index * size..HEADER_SIZE + (index + 1) * size];
+        Some(match self.header.dtype {
+            DType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as TokenId,
+            DType::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        })
+    }
+
+    /// Returns tokens in given range, `None` if the range is out of bounds
+    pub fn tokens(&self, range: Range<usize>) -> Option<Vec<TokenId>> {
+        if range.start > range.end || range.end > self.len() {
+            return None;
+        }
+        Some(range.map(|x| self.get(x).unwrap()).collect())
+    }
+
+    /// Iterates over all tokens
+    pub fn iter(&self) -> impl Iterator<Item = TokenId> + '_ {
+        (0..self.len()).map(|x| self.get(x).unwrap())
+    }
+
+    /// Returns count of documents
+    pub fn document_count(&self) -> usize {
+        self.header.documents as usize
+    }
+
+    /// Returns range of document's tokens,
+    /// `None` if the dataset has no document index
+    pub fn document(&self, index: usize) -> Option<Range<usize>> {
+        if !self.header.has_index || index >= self.document_count() {
+            return None;
+        }
+
+        let start = self.document_start(index);
+        let end = if index + 1 < self.document_count() {
+            self.document_start(index + 1)
+        } else {
+            self.len()
+        };
+        (start <= end && end <= self.len()).then_some(start..end)
+    }
+
+    /// Returns tokens of the document
+    pub fn document_tokens(&self, index: usize) -> Option<Vec<TokenId>> {
+        self.tokens(self.document(index)?)
+    }
+
+    fn document_start(&self, index: usize) -> usize {
+        let offset = HEADER_SIZE + self.len() * self.header.dtype.size() + index * 8;
+        u64::from_le_bytes(self.bytes.as_ref()[offset..offset + 8].try_into().unwrap()) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tokenizer::Fingerprint;
+
+    use crate::DatasetWriter;
+
+    use super::*;
+
+    fn write(dtype: DType, has_index: bool) -> Vec<u8> {
+        let mut writer = DatasetWriter::new(Cursor::new(Vec::new()), dtype, Fingerprint(42))
+            .unwrap()
+            .with_index(has_index);
+        writer.push_document(&[1, 2, 3]).unwrap();
+        writer.push_document(&[]).unwrap();
+        writer.push_document(&[65535, 7]).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn roundtrip() {
+        for dtype in [DType::U16, DType::U32] {
+            let bytes = write(dtype, true);
+            assert_eq!(bytes.len(), HEADER_SIZE + 5 * dtype.size() + 3 * 8);
+
+            let dataset = Dataset::from_bytes(bytes).unwrap();
+            assert_eq!(dataset.header().fingerprint, Fingerprint(42));
+            assert_eq!(dataset.header().dtype, dtype);
+            assert_eq!(dataset.iter().collect::<Vec<_>>(), vec![1, 2, 3, 65535, 7]);
+            assert_eq!(dataset.get(5), None);
+            assert_eq!(dataset.tokens(2..4), Some(vec![3, 65535]));
+            assert_eq!(dataset.document_count(), 3);
+            assert_eq!(dataset.document(0), Some(0..3));
+            assert_eq!(dataset.document(1), Some(3..3));
+            assert_eq!(dataset.document_tokens(2), Some(vec![65535, 7]));
+            assert_eq!(dataset.document(3), None);
+        }
+
+        let dataset = Dataset::from_bytes(write(DType::U16, false)).unwrap();
+        assert_eq!(dataset.len(), 5);
+        assert_eq!(dataset.document(0), None);
+    }
+
+    #[test]
+    fn errors() {
+        let mut writer = DatasetWriter::new(Cursor::new(Vec::new()), DType::U16, Fingerprint(0)).unwrap();
+        assert!(matches!(
+            writer.push_document(&[65536]),
+            Err(DatasetError::TokenOverflow { token: 65536, .. })
+        ));
+
+        let mut bytes = write(DType::U32, true);
+        bytes.pop();
+        assert!(matches!(Dataset::from_bytes(&bytes[..]), Err(DatasetError::Truncated)));
+        bytes[0] = b'X';
+        assert!(matches!(Dataset::from_bytes(&bytes[..]), Err(DatasetError::BadMagic)));
+    }
+
+    #[test]
+    fn mapped() {
+        let path = std::env::temp_dir().join(format!("dataset_mapped_{}.bin", std::process::id()));
+        std::fs::write(&path, write(DType::U32, true)).unwrap();
+
+        let dataset = Dataset::open(&path).unwrap();
+        assert_eq!(dataset.document_tokens(0), Some(vec![1, 2, 3]));
+        assert_eq!(dataset.get(3), Some(65535));
+        drop(dataset);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use tokenizer::{tokenizers::TokenId, Fingerprint};
+
+use crate::{DType, DatasetError, Header};
+
+/// Streams documents into dataset file.
+///
+/// Header is written with zero counts first and rewritten by
+/// [`DatasetWriter::finish`], so output must be seekable.
+pub struct DatasetWriter<W: Write + Seek> {
+    output: W,
+    header: Header,
+
+    /// Position of the header in output
+    start: u64,
+    starts: Vec<u64>,
+}
+
+impl<W: Write + Seek> DatasetWriter<W> {
+    pub fn new(mut output: W, dtype: DType, fingerprint: Fingerprint) -> Result<Self, DatasetError> {
+        let header = Header {
+            dtype,
+            fingerprint,
+            tokens: 0,
+            documents: 0,
+            has_index: true,
+        };
+        let start = output.stream_position()?;
+        output.write_all(&header.to_bytes())?;
+
+        Ok(Self {
+            output,
+            header,
+            start,
+            starts: Vec::new(),
+        })
+    }
+
+    /// Sets whether document index is written, it is written by default
+    pub fn with_index(mut self, has_index: bool) -> Self {
+        self.header.has_index = has_index;
+        self
+    }
+
+    /// Appends tokens of one document
+    pub fn push_document(&mut self, tokens: &[TokenId]) -> Result<(), DatasetError> {
+        let mut bytes = Vec::with_capacity(tokens.len() * self.header.dtype.size());
+        match self.header.dtype {
+            DType::U16 => {
+                for &token in tokens {
+                    let token = u16::try_from(token).map_err(|_| DatasetError::TokenOverflow {
+                        token,
+                        dtype: DType::U16,
+                    })?;
+                    bytes.extend(token.to_le_bytes());
+                }
+            },
+            DType::U32 => bytes.extend(tokens.iter().flat_map(|x| x.to_le_bytes())),
+        }
+        self.output.write_all(&bytes)?;
+
+        self.starts.push(self.header.tokens);
+        self.header.tokens += tokens.len() as u64;
+        self.header.documents += 1;
+        Ok(())
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Writes document index and final header, returns the output
+    pub fn finish(mut self) -> Result<W, DatasetError> {
+        if self.header.has_index {
+            let bytes: Vec<u8> = self.starts.iter().flat_map(|x| x.to_le_bytes()).collect();
+            self.output.write_all(&bytes)?;
+        }
+
+        let end = self.output.stream_position()?;
+        self.output.seek(SeekFrom::Start(self.start))?;
+        self.output.write_all(&self.header.to_bytes())?;
+        self.output.seek(SeekFrom::Start(end))?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::HEADER_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn offset() {
+        // Dataset appended after other content keeps it and patches only its own header
+        let mut output = Cursor::new(b"prefix".to_vec());
+        output.seek(SeekFrom::End(0)).unwrap();
+        let mut writer = DatasetWriter::new(output, DType::U16, Fingerprint(7)).unwrap();
+        writer.push_document(&[1, 2]).unwrap();
+        writer.push_document(&[3]).unwrap();
+        let output = writer.finish().unwrap();
+        assert_eq!(output.position(), output.get_ref().len() as u64);
+
+        let bytes = output.into_inner();
+        assert_eq!(&bytes[..6], b"prefix");
+        let header = Header::from_bytes(&bytes[6..]).unwrap();
+        assert_eq!((header.tokens, header.documents), (3, 2));
+        assert_eq!(header.file_size(), Some(bytes.len() - 6));
+        assert_eq!(&bytes[6 + HEADER_SIZE..6 + HEADER_SIZE + 6], &[1, 0, 2, 0, 3, 0]);
+        assert_eq!(&bytes[6 + HEADER_SIZE + 6..], &[0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn token_overflow() {
+        let mut writer = DatasetWriter::new(Cursor::new(Vec::new()), DType::U16, Fingerprint(7)).unwrap();
+        let error = writer.push_document(&[1, 65536]).unwrap_err();
+        assert!(matches!(error, DatasetError::TokenOverflow { token: 65536, dtype: DType::U16 }));
+        assert_eq!(writer.header().documents, 0);
+    }
+}
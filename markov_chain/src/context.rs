@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use tokenizer::tokenizers::TokenId;
+
+/// Identifier of a context stored in [`ContextTrie`]
+pub type ContextId = u32;
+
+/// Trie of token contexts keyed by their tokens from the most recent one.
+///
+/// Every context is a single [`ContextId`] instead of a `Vec` of tokens,
+/// contexts sharing recent tokens share trie nodes, and suffixes of a context
+/// are its ancestors.
+#[derive(Debug, Clone)]
+pub struct ContextTrie {
+    /// (Parent, token preceding parent context) -> child
+    children: HashMap<(ContextId, TokenId), ContextId>,
+
+    /// Context -> (parent, its oldest token), root has no parent
+    nodes: Vec<(ContextId, TokenId)>,
+}
+
+impl ContextTrie {
+    /// Id of the empty context
+    pub const ROOT: ContextId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            children: HashMap::new(),
+            nodes: vec![(Self::ROOT, 0)],
+        }
+    }
+
+    /// Returns id of the context adding it if it's unknown,
+    /// tokens are in text order
+    pub fn insert(&mut self, context: &[TokenId]) -> ContextId {
+        let mut node = Self::ROOT;
+        for token in context.iter().rev() {
+            node = match self.children.get(&(node, *token)) {
+                Some(child) => *child,
+                None => {
+                    let child = self.nodes.len() as ContextId;
+                    self.nodes.push((node, *token));
+                    self.children.insert((node, *token), child);
+                    child
+                },
+            };
+        }
+        node
+    }
+
+    /// Returns id of the context if it's known
+    pub fn get(&self, context: &[TokenId]) -> Option<ContextId> {
+        context.iter().rev().try_fold(Self::ROOT, |node, token| self.children.get(&(node, *token)).copied())
+    }
+
+    /// Returns ids of known suffixes of the context from the empty one to the longest,
+    /// `i`-th id is the context of the last `i` tokens
+    pub fn suffixes(&self, context: &[TokenId]) -> Vec<ContextId> {
+        let mut ids = vec![Self::ROOT];
+        for token in context.iter().rev() {
+            match self.children.get(&(ids[ids.len() - 1], *token)) {
+                Some(child) => ids.push(*child),
+                None => break,
+            }
+        }
+        ids
+    }
+
+    /// Returns context without its oldest token, `None` for the empty context
+    pub fn parent(&self, id: ContextId) -> Option<ContextId> {
+        (id != Self::ROOT).then(|| self.nodes[id as usize].0)
+    }
+
+    /// Returns count of tokens in the context
+    pub fn depth(&self, id: ContextId) -> usize {
+        let mut depth = 0;
+        let mut node = id;
+        while let Some(parent) = self.parent(node) {
+            node = parent;
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Returns tokens of the context in text order
+    pub fn context(&self, id: ContextId) -> Vec<TokenId> {
+        let mut tokens = Vec::new();
+        let mut node = id;
+        while node != Self::ROOT {
+            let (parent, token) = self.nodes[node as usize];
+            tokens.push(token);
+            node = parent;
+        }
+        tokens
+    }
+
+    /// Returns (parent, oldest token) of every context except the empty one,
+    /// parents always precede their children
+    pub(crate) fn nodes(&self) -> &[(ContextId, TokenId)] {
+        &self.nodes[1..]
+    }
+
+    /// Restores trie from [`ContextTrie::nodes`], `None` if some parent
+    /// doesn't precede its child or a context is repeated
+    pub(crate) fn from_nodes(nodes: &[(ContextId, TokenId)]) -> Option<Self> {
+        let mut trie = Self::new();
+        for (parent, token) in nodes.iter() {
+            let child = trie.nodes.len() as ContextId;
+            if *parent >= child || trie.children.insert((*parent, *token), child).is_some() {
+                return None;
+            }
+            trie.nodes.push((*parent, *token));
+        }
+        Some(trie)
+    }
+
+    /// Returns count of contexts including the empty one
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the trie has only the empty context
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+}
+
+impl Default for ContextTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trie() {
+        let mut trie = ContextTrie::new();
+        let abc = trie.insert(&[1, 2, 3]);
+        let bc = trie.get(&[2, 3]).unwrap();
+        assert_eq!(trie.insert(&[1, 2, 3]), abc);
+        assert_eq!(trie.insert(&[4, 2, 3]), 4);
+        assert_eq!(trie.len(), 5);
+
+        assert_eq!(trie.get(&[1, 2, 3]), Some(abc));
+        assert_eq!(trie.get(&[1, 2]), None);
+        assert_eq!(trie.get(&[]), Some(ContextTrie::ROOT));
+        assert_eq!(trie.context(abc), vec![1, 2, 3]);
+        assert_eq!(trie.context(bc), vec![2, 3]);
+        assert_eq!(trie.suffixes(&[5, 2, 3]), vec![ContextTrie::ROOT, 1, bc]);
+        assert_eq!(trie.parent(abc), Some(bc));
+        assert_eq!(trie.parent(ContextTrie::ROOT), None);
+        assert_eq!(trie.depth(abc), 3);
+    }
+}
@@ -0,0 +1,82 @@
+use std::f64::consts::LN_2;
+
+use tokenizer::tokenizers::TokenId;
+
+use crate::MarkovChain;
+
+/// Quality of a model on held-out text, produced by [`MarkovChain::evaluate`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evaluation {
+    /// Natural logarithm of probability of the text,
+    /// negative infinity if some token has zero probability
+    pub log_likelihood: f64,
+
+    /// Count of predicted tokens
+    pub tokens: usize,
+
+    /// Count of characters of the text
+    pub chars: usize,
+}
+
+impl Evaluation {
+    /// Returns exponent of average negative log-likelihood of a token, lower is better
+    pub fn perplexity(&self) -> f64 {
+        (-self.log_likelihood / self.tokens as f64).exp()
+    }
+
+    /// Returns average count of bits needed to encode a token
+    pub fn bits_per_token(&self) -> f64 {
+        -self.log_likelihood / LN_2 / self.tokens as f64
+    }
+
+    /// Returns average count of bits needed to encode a character,
+    /// unlike perplexity it's comparable between vocabularies
+    pub fn bits_per_char(&self) -> f64 {
+        -self.log_likelihood / LN_2 / self.chars as f64
+    }
+}
+
+impl<R> MarkovChain<R> {
+    /// Evaluates the model on tokens of held-out text of `chars` characters,
+    /// every token is predicted from tokens before it.
+    ///
+    /// Smoothing is required for finite results,
+    /// without it tokens unseen after their context have zero probability.
+    pub fn evaluate(&self, tokens: &[TokenId], chars: usize) -> Evaluation {
+        let log_likelihood = (0..tokens.len())
+            .map(|i| self.probability(&tokens[..i], tokens[i]).ln())
+            .sum();
+
+        Evaluation {
+            log_likelihood,
+            tokens: tokens.len(),
+            chars,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Smoothing;
+
+    use super::*;
+
+    #[test]
+    fn evaluate() {
+        let mut chain = MarkovChain::with_order(1);
+        chain.train_chain(&[0, 1, 0, 1, 0, 1]);
+        chain.smoothing = Smoothing::Additive { alpha: 1.0 };
+
+        // Context of the first token is too short, so it has uniform probability 1/2,
+        // then 1 after 0 has (3 + 1) / (3 + 2)
+        let evaluation = chain.evaluate(&[0, 1], 4);
+        let likelihood: f64 = 0.5 * 0.8;
+        assert!((evaluation.log_likelihood - likelihood.ln()).abs() < 1e-12);
+        assert!((evaluation.perplexity() - (1.0 / likelihood).sqrt()).abs() < 1e-12);
+        assert!((evaluation.bits_per_token() - evaluation.perplexity().log2()).abs() < 1e-12);
+        assert!((evaluation.bits_per_char() - evaluation.bits_per_token() / 2.0).abs() < 1e-12);
+
+        chain.smoothing = Smoothing::None;
+        assert_eq!(chain.evaluate(&[0, 0], 2).perplexity(), f64::INFINITY);
+    }
+}
@@ -1,67 +1,550 @@
+mod context;
+mod eval;
+mod model_file;
+mod sampling;
+mod smoothing;
 mod weighted_list;
+mod weighted_tree;
 
+pub use context::*;
+pub use eval::*;
+pub use model_file::*;
+pub use sampling::*;
+pub use smoothing::*;
 pub use weighted_list::*;
+pub use weighted_tree::*;
 
 use std::collections::HashMap;
 
 use rand::{rngs::ThreadRng, Rng};
-use tokenizer::tokenizers::*;
+use tokenizer::{tokenizers::*, Fingerprint, FingerprintMismatch, IdRemap, Vocabulary};
 
 /// Simple random based language model which next token
-/// prediction based only on one previous token and probabilities of
-/// next tokens
-pub struct MarkovChain {
-    pub rand: ThreadRng,
-    pub chain: HashMap<TokenId, WeightedVec<TokenId>>,
+/// prediction based only on `order` previous tokens and probabilities of
+/// next tokens.
+///
+/// Tokens are generated with random generator `R`, a seeded one
+/// like `StdRng` makes generation reproducible.
+pub struct MarkovChain<R = ThreadRng> {
+    pub rand: R,
+
+    /// Count of previous tokens next token depends on
+    order: usize,
+
+    /// Estimation of next token probabilities, see [`Smoothing`],
+    /// checked by [`MarkovChain::set_smoothing`]
+    smoothing: Smoothing,
+
+    /// Transformation of smoothed probabilities before next token is chosen,
+    /// checked by [`MarkovChain::set_sampling`]
+    sampling: Sampling,
+
+    /// Contexts of up to `order` tokens seen in training
+    contexts: ContextTrie,
+
+    /// Context -> next tokens weighted by count, sorted by `TokenId`.
+    /// Contains contexts of every length up to `order`.
+    chain: HashMap<ContextId, WeightedTree<TokenId>>,
+
+    /// Statistics of Kneser-Ney smoothing, updated after training
+    kneser_ney: KneserNey,
+
+    /// Discounts of Katz smoothing, updated after training
+    katz: Katz,
+
+    /// Count of `TokenId`s distributions are defined over
+    vocab_size: usize,
+
+    /// Fingerprint of vocabulary used for training
+    pub vocab_fingerprint: Option<Fingerprint>,
 }
 
 impl MarkovChain {
+    /// Creates chain of order 1, next token depends only on the previous one
     pub fn new() -> Self {
+        Self::with_order(1)
+    }
+
+    /// Creates chain where next token depends on `order` previous tokens
+    pub fn with_order(order: usize) -> Self {
+        Self::with_rng(order, rand::thread_rng())
+    }
+}
+
+impl<R> MarkovChain<R> {
+    /// Creates chain of given order generating tokens with given random generator
+    pub fn with_rng(order: usize, rand: R) -> Self {
         Self {
-            rand: rand::thread_rng(),
+            rand,
+            order,
+            smoothing: Smoothing::default(),
+            sampling: Sampling::default(),
+            contexts: ContextTrie::new(),
             chain: HashMap::new(),
+            kneser_ney: KneserNey::default(),
+            katz: Katz::default(),
+            vocab_size: 0,
+            vocab_fingerprint: None,
         }
     }
 
-    /// Creates token-to-token-set distribution based on 
-    /// given sequence of `TokenId`
-    pub fn train_chain(&mut self, corpus: &Vec<TokenId>) {
-        // Token -> (Token -> Count)
-        let mut sets: HashMap<TokenId, HashMap<TokenId, usize>> = HashMap::new();
-           
-        for window in corpus[..].windows(2) {
-            let token = window[0];
-            let next = window[1];
-
-            if let Some(set) = sets.get_mut(&token) {
-                if let Some(cnt) = set.get_mut(&next) {
-                    *cnt += 1;
-                } else {
-                    set.insert(next, 1);
+    /// Returns the same chain generating tokens with another random generator
+    pub fn with_rand<T>(self, rand: T) -> MarkovChain<T> {
+        MarkovChain {
+            rand,
+            order: self.order,
+            smoothing: self.smoothing,
+            sampling: self.sampling,
+            contexts: self.contexts,
+            chain: self.chain,
+            kneser_ney: self.kneser_ney,
+            katz: self.katz,
+            vocab_size: self.vocab_size,
+            vocab_fingerprint: self.vocab_fingerprint,
+        }
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    /// Changes estimation of next token probabilities, fails if its parameter is out of range
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) -> Result<(), String> {
+        smoothing.validate()?;
+        self.smoothing = smoothing;
+        Ok(())
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    /// Changes how next tokens are chosen, fails if sampling parameters are out of their ranges
+    pub fn set_sampling(&mut self, sampling: Sampling) -> Result<(), String> {
+        sampling.validate()?;
+        self.sampling = sampling;
+        Ok(())
+    }
+
+    /// Returns contexts of up to `order` tokens seen in training
+    pub fn contexts(&self) -> &ContextTrie {
+        &self.contexts
+    }
+
+    /// Returns next tokens weighted by count of every context seen in training,
+    /// they are changed only by training methods, which keep smoothing statistics up to date
+    pub fn chain(&self) -> &HashMap<ContextId, WeightedTree<TokenId>> {
+        &self.chain
+    }
+
+    /// Returns count of `TokenId`s smoothed distributions are defined over,
+    /// ids from zero up to the biggest id of the vocabulary or training corpus
+    pub fn vocab_size(&self) -> usize {
+        self.vocab_size
+    }
+
+    /// Remembers vocabulary whose `TokenId`s the chain is trained on
+    pub fn set_vocabulary(&mut self, vocab: &Vocabulary) {
+        self.vocab_fingerprint = Some(vocab.fingerprint());
+        self.vocab_size = vocab.iter().last().map_or(0, |(id, _)| id as usize + 1);
+    }
+
+    /// Checks that the chain was trained on `TokenId`s of given vocabulary,
+    /// chain without remembered vocabulary accepts any vocabulary
+    pub fn check_vocabulary(&self, vocab: &Vocabulary) -> Result<(), FingerprintMismatch> {
+        match self.vocab_fingerprint {
+            Some(expected) => expected.verify(vocab.fingerprint()),
+            None => Ok(()),
+        }
+    }
+
+    /// Creates context-to-token-set distribution based on
+    /// given sequence of `TokenId`, counts of previous training are kept.
+    ///
+    /// Every token is counted after contexts of all lengths up to `order`,
+    /// shorter contexts are used by smoothing.
+    pub fn train_chain(&mut self, corpus: &[TokenId]) {
+        // Context -> (Token -> Count)
+        let mut sets: HashMap<ContextId, HashMap<TokenId, usize>> = HashMap::new();
+
+        for (i, next) in corpus.iter().enumerate() {
+            let mut context = Some(self.contexts.insert(&corpus[i.saturating_sub(self.order)..i]));
+            while let Some(token) = context {
+                let set = sets.entry(token).or_insert_with(|| counts(self.chain.get(&token)));
+                *set.entry(*next).or_default() += 1;
+                context = self.contexts.parent(token);
+            }
+            self.vocab_size = self.vocab_size.max(*next as usize + 1);
+        }
+        for (token, set) in sets {
+            self.chain.insert(token, weighted(set));
+        }
+        self.kneser_ney = KneserNey::new(self);
+        self.katz = Katz::new(self);
+    }
+
+    /// Subtracts counts of given sequence of `TokenId`, undoing [`MarkovChain::train_chain`]
+    /// on it, counts are decremented in place and never drop below zero.
+    ///
+    /// Transitions whose count drops to zero are removed, longer contexts don't keep
+    /// transitions their suffixes lost, e.g. after forgetting text that wasn't trained on.
+    pub fn forget_chain(&mut self, corpus: &[TokenId]) {
+        let mut changed = Vec::new();
+        for (i, next) in corpus.iter().enumerate() {
+            let mut context = self.contexts.get(&corpus[i.saturating_sub(self.order)..i]);
+            while let Some(token) = context {
+                if let Some(set) = self.chain.get_mut(&token) {
+                    if let Ok(index) = set.elements.binary_search(next) {
+                        if set.weight(index) > 0 {
+                            set.decrement(index, 1);
+                            changed.push(token);
+                        }
+                    }
                 }
-            } else {
-                let mut set = HashMap::new();
-                set.insert(next, 1);
-                sets.insert(token, set);
+                context = self.contexts.parent(token);
             }
         }
-        for (token, set) in sets.iter() {
-            let mut a: WeightedVec<TokenId> = WeightedVec::new();
-            for (el, cnt) in set.iter() {
-                a.push(*cnt, *el);
+        changed.sort_unstable();
+        changed.dedup();
+        for token in changed {
+            self.drop_rare(token, 1);
+        }
+        self.clamp_to_suffixes();
+        self.kneser_ney = KneserNey::new(self);
+        self.katz = Katz::new(self);
+    }
+
+    /// Lowers counts of transitions to counts of the same transitions after the context
+    /// without its oldest token, smoothing relies on suffixes having all transitions
+    fn clamp_to_suffixes(&mut self) {
+        let mut contexts: Vec<ContextId> = self.chain.keys().copied().collect();
+        // Parents are added to the trie before their children, so they are clamped first
+        contexts.sort_unstable();
+        for context in contexts {
+            let Some(parent) = self.contexts.parent(context) else {
+                continue;
+            };
+            let limits: Vec<usize> = match self.chain.get(&parent) {
+                Some(suffix) => self.chain[&context].elements.iter().map(|x| count(suffix, *x)).collect(),
+                None => vec![0; self.chain[&context].len()],
+            };
+            let set = self.chain.get_mut(&context).unwrap();
+            let mut clamped = false;
+            for (i, limit) in limits.into_iter().enumerate() {
+                if set.weight(i) > limit {
+                    set.set_weight(i, limit);
+                    clamped = true;
+                }
             }
-            self.chain.insert(*token, a);
+            if clamped {
+                self.drop_rare(context, 1);
+            }
+        }
+    }
+
+    /// Removes transitions seen less than `min_count` times in training,
+    /// which shrinks the model at the cost of forgetting rare continuations
+    pub fn prune(&mut self, min_count: usize) {
+        let contexts: Vec<ContextId> = self.chain.keys().copied().collect();
+        for token in contexts {
+            self.drop_rare(token, min_count);
         }
+        self.kneser_ney = KneserNey::new(self);
+        self.katz = Katz::new(self);
     }
 
-    /// Generates next token depending on previous `TokenId`
-    pub fn next_token(&mut self, token: TokenId) -> Option<TokenId> {
-        if let Some(set) = self.chain.get(&token) {
-            let size = set.next;
-            let index = self.rand.gen_range(0..size);
-            set.choose(index).cloned()
-        } else {
-            None
+    /// Removes next tokens of the context counted less than `min_count` times,
+    /// and the context itself if none are left
+    fn drop_rare(&mut self, context: ContextId, min_count: usize) {
+        if let Some(set) = self.chain.get_mut(&context) {
+            set.retain(|_, count| count >= min_count);
+            if set.is_empty() {
+                self.chain.remove(&context);
+            }
         }
     }
+
+    /// Returns weighted next tokens of the context,
+    /// only the last `order` tokens of the context are used
+    pub fn successors(&self, context: &[TokenId]) -> Option<&WeightedTree<TokenId>> {
+        self.chain.get(&self.context_id(context)?)
+    }
+
+    fn context_id(&self, context: &[TokenId]) -> Option<ContextId> {
+        let start = context.len().checked_sub(self.order)?;
+        self.contexts.get(&context[start..])
+    }
+
+    /// Converts `TokenId`s of the chain after [`tokenizer::Vocabulary::compact`],
+    /// transitions from or to removed tokens are dropped.
+    ///
+    /// Remembered vocabulary should be updated with [`MarkovChain::set_vocabulary`].
+    pub fn remap(&mut self, remap: &IdRemap) {
+        let chain = std::mem::take(&mut self.chain);
+        let contexts = std::mem::take(&mut self.contexts);
+        self.vocab_size = 0;
+        for (token, set) in chain {
+            let context: Option<Vec<TokenId>> = contexts.context(token).into_iter().map(|x| remap.get(x)).collect();
+            let Some(context) = context else {
+                continue;
+            };
+
+            let mut a = HashMap::new();
+            for (el, count) in set.iter() {
+                if let Some(el) = remap.get(*el) {
+                    a.insert(el, count);
+                    self.vocab_size = self.vocab_size.max(el as usize + 1);
+                }
+            }
+            if !a.is_empty() {
+                self.chain.insert(self.contexts.insert(&context), weighted(a));
+            }
+        }
+        self.kneser_ney = KneserNey::new(self);
+        self.katz = Katz::new(self);
+    }
+}
+
+impl<R: Rng> MarkovChain<R> {
+    /// Generates next token depending on the last `order` tokens of the context.
+    ///
+    /// Without smoothing returns `None` if the context is shorter or wasn't seen in training,
+    /// smoothed models choose from [`MarkovChain::distribution`] transformed by [`Sampling`].
+    pub fn next_token(&mut self, context: &[TokenId]) -> Option<TokenId> {
+        if self.smoothing != Smoothing::None || self.sampling != Sampling::default() {
+            let mut distribution = self.distribution(context);
+            self.sampling.apply(&mut distribution);
+            return choose_weighted(&mut self.rand, &distribution);
+        }
+
+        let set = self.chain.get(&self.context_id(context)?)?;
+        set.sample(&mut self.rand).copied()
+    }
+}
+
+impl Default for MarkovChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns counts of next tokens
+fn counts(set: Option<&WeightedTree<TokenId>>) -> HashMap<TokenId, usize> {
+    set.map(|x| x.iter().map(|(token, count)| (*token, count)).collect())
+        .unwrap_or_default()
+}
+
+/// Returns next tokens weighted by counts and sorted by `TokenId`
+fn weighted(counts: HashMap<TokenId, usize>) -> WeightedTree<TokenId> {
+    let mut counts: Vec<(TokenId, usize)> = counts.into_iter().collect();
+    counts.sort_unstable();
+    let (tokens, counts) = counts.into_iter().unzip();
+    WeightedTree::from_weights(tokens, counts)
+}
+
+/// Returns count of token in weighted next tokens sorted by `TokenId`
+fn count(set: &WeightedTree<TokenId>, token: TokenId) -> usize {
+    set.elements.binary_search(&token).map_or(0, |i| set.weight(i))
+}
+
+/// Chooses index of a weight proportionally to it, `None` if all weights are zero
+fn choose_weighted(rand: &mut impl Rng, weights: &[f64]) -> Option<TokenId> {
+    let total: f64 = weights.iter().sum();
+    if total.is_nan() || total <= 0.0 {
+        return None;
+    }
+    let mut point = rand.gen::<f64>() * total;
+    let mut last = None;
+    for (i, weight) in weights.iter().enumerate() {
+        if *weight > 0.0 {
+            if point < *weight {
+                return Some(i as TokenId);
+            }
+            point -= weight;
+            last = Some(i as TokenId);
+        }
+    }
+    // Rounding errors can leave a tiny remainder
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+    use tokenizer::VocabularyBuilder;
+
+    use super::*;
+
+    #[test]
+    fn remap() {
+        let mut vocab = VocabularyBuilder::new()
+            .add_token("a".to_string(), 3)
+            .add_token("b".to_string(), 5)
+            .add_token("c".to_string(), 8)
+            .build();
+        let mut chain = MarkovChain::new();
+        chain.train_chain(&[3, 5, 3, 5, 8, 3]);
+
+        vocab.remove_token("c");
+        chain.remap(&vocab.compact());
+
+        // Empty context and contexts of both tokens
+        assert_eq!(chain.chain.len(), 3);
+        assert_eq!(chain.successors(&[0]).unwrap().elements, vec![1]);
+        assert_eq!(chain.successors(&[0]).unwrap().total(), 2);
+        assert_eq!(chain.successors(&[1]).unwrap().elements, vec![0]);
+        assert_eq!(chain.successors(&[1]).unwrap().total(), 1);
+        assert_eq!(chain.next_token(&[0]), Some(1));
+    }
+
+    #[test]
+    fn order() {
+        let mut chain = MarkovChain::with_order(2);
+        chain.train_chain(&[1, 2, 3, 1, 2, 3, 4, 2, 5]);
+
+        // After "2" alone both 3 and 5 follow, after "1 2" only 3
+        assert_eq!(chain.successors(&[1, 2]).unwrap().elements, vec![3]);
+        assert_eq!(chain.successors(&[1, 2]).unwrap().total(), 2);
+        assert_eq!(chain.successors(&[4, 2]).unwrap().elements, vec![5]);
+        assert_eq!(chain.next_token(&[9, 1, 2]), Some(3));
+        assert_eq!(chain.next_token(&[2]), None);
+        assert_eq!(chain.next_token(&[2, 1]), None);
+
+        // Smoothed model backs off to shorter contexts
+        assert!(chain.set_smoothing(Smoothing::Additive { alpha: 0.0 }).is_err());
+        chain.set_smoothing(Smoothing::KneserNey).unwrap();
+        assert!(chain.next_token(&[2, 1]).is_some());
+        assert!(chain.next_token(&[]).is_some());
+
+        let mut unigram = MarkovChain::with_order(0);
+        unigram.train_chain(&[7, 7]);
+        assert_eq!(unigram.next_token(&[]), Some(7));
+    }
+
+    #[test]
+    fn forget() {
+        let mut chain = MarkovChain::with_order(2);
+        chain.train_chain(&[1, 2, 3, 1, 2, 4]);
+        chain.train_chain(&[5, 1, 2, 3]);
+
+        chain.forget_chain(&[1, 2, 3]);
+        assert_eq!(chain.successors(&[1, 2]).unwrap().weights(), vec![1, 1]);
+        assert_eq!(chain.successors(&[5, 1]).unwrap().elements, vec![2]);
+
+        // Count of 4 drops to zero, 7 was never seen and is ignored
+        chain.forget_chain(&[1, 2, 4, 7]);
+        assert_eq!(chain.successors(&[1, 2]).unwrap().elements, vec![3]);
+        assert!(chain.chain.values().all(|set| set.weights().iter().all(|x| *x > 0)));
+
+        chain.smoothing = Smoothing::KneserNey;
+        let total: f64 = chain.distribution(&[1, 2]).iter().sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        // "1 2" was never trained, context "1" loses its only transition and "5 1" can't keep it
+        let mut chain = MarkovChain::with_order(2);
+        chain.train_chain(&[5, 1, 2]);
+        chain.forget_chain(&[1, 2]);
+        assert!(chain.successors(&[1]).is_none());
+        assert!(chain.successors(&[5, 1]).is_none());
+        chain.smoothing = Smoothing::KneserNey;
+        assert!(chain.probability(&[5, 1], 2) > 0.0);
+        let mut bytes = Vec::new();
+        chain.write(&mut bytes).unwrap();
+        assert!(MarkovChain::read(bytes.as_slice()).is_ok());
+    }
+
+    #[test]
+    fn prune() {
+        let mut chain = MarkovChain::new();
+        chain.train_chain(&[1, 2, 1, 2, 1, 3, 4]);
+        chain.prune(2);
+
+        assert_eq!(chain.successors(&[1]).unwrap().elements, vec![2]);
+        assert!(chain.successors(&[3]).is_none());
+        assert_eq!(chain.chain[&ContextTrie::ROOT].elements, vec![1, 2]);
+        assert_eq!(chain.next_token(&[2]), Some(1));
+    }
+
+    #[test]
+    fn sampling() {
+        let mut chain = MarkovChain::with_order(1);
+        chain.train_chain(&[1, 2, 1, 2, 1, 3, 3, 1]);
+
+        // Greedy decoding follows the most frequent transitions, also without smoothing
+        chain.set_sampling(Sampling::greedy()).unwrap();
+        let mut context = vec![3];
+        for _ in 0..4 {
+            let token = chain.next_token(&context).unwrap();
+            context.push(token);
+        }
+        assert_eq!(context, vec![3, 1, 2, 1, 2]);
+        assert_eq!(chain.next_token(&[4]), None);
+
+        chain.smoothing = Smoothing::KneserNey;
+        chain.set_sampling(Sampling { top_k: 1, ..Sampling::default() }).unwrap();
+        assert_eq!(chain.next_token(&[1]), Some(2));
+        chain.set_sampling(Sampling { min_p: 0.9, ..Sampling::default() }).unwrap();
+        assert_eq!(chain.next_token(&[2]), Some(1));
+
+        // Invalid sampling is rejected and the previous one is kept
+        assert!(chain.set_sampling(Sampling { temperature: f64::NAN, ..Sampling::default() }).is_err());
+        assert!(chain.set_sampling(Sampling { top_p: -0.5, ..Sampling::default() }).is_err());
+        assert_eq!(chain.sampling().min_p, 0.9);
+    }
+
+    #[test]
+    fn seed() {
+        let corpus = [1, 2, 3, 1, 2, 4, 2, 1, 3, 3, 4, 1, 2, 3];
+        let generate = |smoothing: Smoothing, seed: u64| {
+            let mut chain = MarkovChain::with_rng(2, StdRng::seed_from_u64(seed));
+            chain.smoothing = smoothing;
+            chain.train_chain(&corpus);
+            let mut context = vec![1, 2];
+            for _ in 0..12 {
+                let token = chain.next_token(&context).unwrap();
+                context.push(token);
+            }
+            context
+        };
+
+        assert_eq!(generate(Smoothing::None, 42), vec![1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 2]);
+        assert_eq!(generate(Smoothing::KneserNey, 42), vec![1, 2, 3, 1, 2, 3, 1, 2, 4, 2, 1, 0, 4, 2]);
+        assert_eq!(generate(Smoothing::KneserNey, 7), generate(Smoothing::KneserNey, 7));
+
+        // Changing the generator keeps the trained chain
+        let chain = MarkovChain::with_order(2);
+        let mut chain = chain.with_rand(StdRng::seed_from_u64(42));
+        chain.train_chain(&corpus);
+        let mut context = vec![1, 2];
+        for _ in 0..12 {
+            let token = chain.next_token(&context).unwrap();
+            context.push(token);
+        }
+        assert_eq!(context, generate(Smoothing::None, 42));
+    }
+
+    #[test]
+    fn check_vocabulary() {
+        let vocab = VocabularyBuilder::new()
+            .try_add_token("a".to_string())
+            .try_add_token("b".to_string())
+            .build();
+        let other = VocabularyBuilder::new()
+            .try_add_token("b".to_string())
+            .try_add_token("a".to_string())
+            .build();
+
+        let mut chain = MarkovChain::new();
+        assert!(chain.check_vocabulary(&other).is_ok());
+
+        chain.set_vocabulary(&vocab);
+        assert!(chain.check_vocabulary(&vocab).is_ok());
+        let error = chain.check_vocabulary(&other).unwrap_err();
+        assert_eq!(error.expected, vocab.fingerprint());
+        assert_eq!(error.found, other.fingerprint());
+    }
 }
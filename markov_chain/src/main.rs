@@ -1,78 +1,173 @@
-use std::{fs, io::{stdin, stdout, Write}, path::PathBuf, str::FromStr, time::Instant};
+use std::{error::Error, fs, io::{stdin, stdout, Write}, path::{Path, PathBuf}, time::Instant};
 
-use markov_chain::MarkovChain;
-use tokenizer::{Vocabulary, tokenizers::BpeTokenizer, tokenizers::Tokenizer};
+use cli_args::{Arg, Command, Matches, ParseError};
+use markov_chain::{MarkovChain, Sampling, Smoothing};
+use rand::{rngs::StdRng, SeedableRng};
+use tokenizer::TokenizerPipeline;
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-
-    let vocab_path = if let Some(path) = args.get(1) {
-        if let Some(x) = PathBuf::from_str(path).ok() {
-            x
-        } else {
-            eprintln!("Can't read file {}", path);
-            return
-        }
-    } else {
-        eprintln!("No vocab file");
-        return
-    };
+const SMOOTHING_HELP: &str = "none, \"additive [alpha]\", kneser-ney, \"stupid-backoff [alpha]\" or katz";
 
-    let corpus_path = if let Some(path) = args.get(2) {
-        if let Some(x) = PathBuf::from_str(path).ok() {
-            x
-        } else {
-            eprintln!("Can't read file {}", path);
-            return
-        }
-    } else {
-        eprintln!("No corpus file");
-        return
-    };
+fn cli() -> Command {
+    Command::new("markov_chain")
+        .about("Markov chain language model")
+        .subcommand(Command::new("train")
+            .about("Trains chain on corpus and saves it")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("corpus").help("Training corpus"))
+            .arg(Arg::positional("model_file").help("Output model file"))
+            .arg(Arg::option("order").short('n').value_name("n").default("1").help("Count of previous tokens next token depends on"))
+            .arg(Arg::option("smoothing").short('s').value_name("name").default("kneser-ney").help(SMOOTHING_HELP)))
+        .subcommand(Command::new("generate")
+            .about("Continues prompts from stdin")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("model_file"))
+            .arg(Arg::option("smoothing").short('s').value_name("name").help("Overrides smoothing of the model"))
+            .arg(Arg::option("seed").value_name("n").help("Seed of random generator, same seed generates same text"))
+            .arg(Arg::option("temperature").short('t').value_name("t").default("1").help("Lower is more predictable, 0 always chooses the most probable token"))
+            .arg(Arg::option("top-k").value_name("k").default("0").help("Chooses only from k most probable tokens, 0 disables"))
+            .arg(Arg::option("top-p").value_name("p").default("1").help("Chooses only from the most probable tokens with total probability p"))
+            .arg(Arg::option("min-p").value_name("p").default("0").help("Drops tokens less probable than p times the most probable one"))
+            .arg(Arg::option("typical-p").value_name("p").default("1").help("Chooses only from tokens of typical probability with total probability p")))
+        .subcommand(Command::new("eval")
+            .about("Measures chain on held-out text")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("model_file"))
+            .arg(Arg::positional("held_out").help("Text the chain is evaluated on, e.g. content/vm2.txt"))
+            .arg(Arg::option("smoothing").short('s').value_name("name").help("Overrides smoothing of the model")))
+}
+
+fn train_subcommand(pipeline: &TokenizerPipeline, args: &Matches) -> Result<(), Box<dyn Error>> {
+    let corpus_path: PathBuf = args.get("corpus")?;
+    let model_path: PathBuf = args.get("model_file")?;
 
-    let vocab = Vocabulary::from_str(&std::fs::read_to_string(vocab_path).unwrap());
-    
     println!("Tokenizing corpus...");
-    let bpe = BpeTokenizer::new();
-    let corpus = fs::read_to_string(corpus_path).unwrap();
-    let tokens = bpe.tokenize(&corpus, &vocab);
+    let corpus = fs::read_to_string(corpus_path)?;
+    let tokens = pipeline.tokenize(&corpus);
 
     println!("Training chain...");
-    let mut chain = MarkovChain::new();
-    stdout().flush().unwrap();
+    let mut chain = MarkovChain::with_order(args.get("order")?);
+    chain.set_smoothing(args.get("smoothing")?)?;
     chain.train_chain(&tokens);
+    chain.set_vocabulary(pipeline.vocab());
+
+    chain.save(&model_path)?;
+    println!("Saved {} contexts to {}", chain.contexts().len(), model_path.display());
+    Ok(())
+}
+
+/// Loads model checking that it was trained on the pipeline's vocabulary
+fn load(pipeline: &TokenizerPipeline, args: &Matches) -> Result<MarkovChain, Box<dyn Error>> {
+    let model_path: PathBuf = args.get("model_file")?;
+    let mut chain = MarkovChain::load(&model_path).map_err(|e| format!("{}: {e}", model_path.display()))?;
+    chain.check_vocabulary(pipeline.vocab())?;
+    if let Some(smoothing) = args.get_opt::<Smoothing>("smoothing")? {
+        chain.set_smoothing(smoothing)?;
+    }
+    Ok(chain)
+}
+
+fn generate_subcommand(pipeline: &TokenizerPipeline, chain: MarkovChain, args: &Matches) -> Result<(), Box<dyn Error>> {
+    let vocab = pipeline.vocab();
+    let mut chain = chain.with_rand(match args.get_opt("seed")? {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    });
+    chain.set_sampling(Sampling {
+        temperature: args.get("temperature")?,
+        top_k: args.get("top-k")?,
+        top_p: args.get("top-p")?,
+        min_p: args.get("min-p")?,
+        typical_p: args.get("typical-p")?,
+    })?;
 
     println!("Ready!\n");
     let mut buffer = String::new();
     loop {
         buffer.clear();
-        if let Err(e) = stdin().read_line(&mut buffer) {
-            eprintln!("{e}");
-            continue;
+        match stdin().read_line(&mut buffer) {
+            Ok(0) => break,
+            Ok(_) => {},
+            Err(e) => {
+                eprintln!("{e}");
+                continue;
+            },
         }
         buffer = buffer.replace('\n', "");
-        let tokens = bpe.tokenize(&buffer, &vocab);
-        let mut prev = tokens[tokens.len()-1];
+        // Without post-processor, its end token would end the prompt
+        let mut context = pipeline.model().tokenize(&buffer, vocab);
         print!("\x1b[1A{}", &buffer);
-        stdout().flush().unwrap();
+        stdout().flush()?;
 
         let mut time = Instant::now();
 
         for _ in 0..256 {
-            let id = match chain.next_token(prev) {
+            let id = match chain.next_token(&context) {
                 Some(id) => id,
                 None => break,
             };
-            prev = id;
-            let token = vocab.get_token(id).unwrap();
+            context.push(id);
+            let token = vocab.get_token(id).unwrap_or_default();
             while time.elapsed().as_millis() < 50 { }
 
             print!("{}", token);
             print!("\x1b[0m");
-            stdout().flush().unwrap();
+            stdout().flush()?;
             time = Instant::now();
         }
         print!("\n\n");
-        stdout().flush().unwrap();
+        stdout().flush()?;
+    }
+    Ok(())
+}
+
+fn eval_subcommand(pipeline: &TokenizerPipeline, chain: &MarkovChain, held_out: &Path) -> Result<(), Box<dyn Error>> {
+    let text = fs::read_to_string(held_out)?;
+    let tokens = pipeline.tokenize(&text);
+    let chars = text.chars().count();
+    // Metrics are averages over tokens and chars
+    if tokens.is_empty() || chars == 0 {
+        return Err(format!("{}: held-out text is empty", held_out.display()).into());
+    }
+
+    println!("Evaluating...");
+    let evaluation = chain.evaluate(&tokens, chars);
+    println!("order:          {}", chain.order());
+    println!("smoothing:      {}", chain.smoothing());
+    println!("tokens:         {}", evaluation.tokens);
+    println!("chars:          {}", evaluation.chars);
+    println!("log-likelihood: {:.2}", evaluation.log_likelihood);
+    println!("perplexity:     {:.3}", evaluation.perplexity());
+    println!("bits per token: {:.4}", evaluation.bits_per_token());
+    println!("bits per char:  {:.4}", evaluation.bits_per_char());
+    if chain.smoothing() == Smoothing::None && evaluation.log_likelihood.is_infinite() {
+        println!("Some tokens have zero probability, use --smoothing");
+    }
+    Ok(())
+}
+
+fn run(matches: &Matches) -> Result<(), Box<dyn Error>> {
+    let Some((command, args)) = matches.subcommand() else {
+        unreachable!()
+    };
+    let vocab_path: PathBuf = args.get("vocab_file")?;
+    let pipeline = TokenizerPipeline::load(&vocab_path)?;
+
+    match command {
+        "train" => train_subcommand(&pipeline, args),
+        "generate" => generate_subcommand(&pipeline, load(&pipeline, args)?, args),
+        "eval" => eval_subcommand(&pipeline, &load(&pipeline, args)?, &args.get::<PathBuf>("held_out")?),
+        _ => unreachable!(),
+    }
+}
+
+fn main() {
+    let matches = cli().parse_env().unwrap_or_else(|e| e.exit());
+
+    if let Err(e) = run(&matches) {
+        if let Some(e) = e.downcast_ref::<ParseError>() {
+            e.exit();
+        }
+        eprintln!("{e}");
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,406 @@
+//! Binary file of a trained [`MarkovChain`].
+//!
+//! Layout, fixed size numbers are little-endian, the rest are unsigned LEB128 varints:
+//!
+//! | size   | content                                                      |
+//! |--------|--------------------------------------------------------------|
+//! | 4      | magic `SMMC`                                                 |
+//! | 2      | format version                                               |
+//! | 1      | flags, bit 0 is set if vocabulary fingerprint is present      |
+//! | 1      | smoothing: 0 none, 1 additive, 2 Kneser-Ney, 3 stupid backoff, |
+//! |        | 4 Katz                                                       |
+//! | 8      | `f64` parameter of smoothing, zero if it has none            |
+//! | 8      | fingerprint of vocabulary used for training                  |
+//! | varint | order                                                        |
+//! | varint | vocabulary size                                              |
+//! | varint | count of contexts except the empty one                       |
+//! | varints | parent and oldest token of every context                    |
+//! | varint | count of contexts with next tokens                           |
+//! | varints | for every such context: its id, count of next tokens, then  |
+//! |        | difference with the previous next token and count of every one |
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use tokenizer::{tokenizers::TokenId, Fingerprint};
+
+use crate::{smoothing::{Katz, KneserNey}, ContextId, ContextTrie, MarkovChain, Smoothing, WeightedTree};
+
+pub const MODEL_MAGIC: [u8; 4] = *b"SMMC";
+pub const MODEL_VERSION: u16 = 1;
+
+const FLAG_FINGERPRINT: u8 = 1;
+
+/// Bigger orders and vocabularies are rejected by [`MarkovChain::read`],
+/// buffers of their size are allocated when the model is used
+pub const MAX_ORDER: usize = 256;
+pub const MAX_VOCAB_SIZE: usize = 1 << 24;
+
+/// Error of reading or writing model file
+#[derive(Debug)]
+pub enum ModelError {
+    Io(std::io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnknownSmoothing(u8),
+
+    /// File ends in the middle of the model
+    Truncated,
+
+    /// File content is inconsistent, e.g. refers to unknown context
+    Corrupted(&'static str),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Io(e) => write!(f, "{e}"),
+            ModelError::BadMagic => write!(f, "not a Markov chain model file"),
+            ModelError::UnsupportedVersion(x) => write!(f, "unsupported model version {x}, expected {MODEL_VERSION}"),
+            ModelError::UnknownSmoothing(x) => write!(f, "unknown smoothing {x}"),
+            ModelError::Truncated => write!(f, "model file is truncated"),
+            ModelError::Corrupted(x) => write!(f, "model file is corrupted: {x}"),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModelError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ModelError {
+    fn from(value: std::io::Error) -> Self {
+        ModelError::Io(value)
+    }
+}
+
+impl<R> MarkovChain<R> {
+    /// Writes the model, equal models always produce identical output
+    pub fn write(&self, mut output: impl Write) -> Result<(), ModelError> {
+        let (smoothing, parameter) = match self.smoothing {
+            Smoothing::None => (0, 0.0),
+            Smoothing::Additive { alpha } => (1, alpha),
+            Smoothing::KneserNey => (2, 0.0),
+            Smoothing::StupidBackoff { alpha } => (3, alpha),
+            Smoothing::Katz => (4, 0.0),
+        };
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MODEL_MAGIC);
+        bytes.extend_from_slice(&MODEL_VERSION.to_le_bytes());
+        bytes.push(if self.vocab_fingerprint.is_some() { FLAG_FINGERPRINT } else { 0 });
+        bytes.push(smoothing);
+        bytes.extend_from_slice(&parameter.to_le_bytes());
+        bytes.extend_from_slice(&self.vocab_fingerprint.map_or(0, |x| x.0).to_le_bytes());
+
+        write_varint(&mut bytes, self.order as u64);
+        write_varint(&mut bytes, self.vocab_size as u64);
+        let nodes = self.contexts.nodes();
+        write_varint(&mut bytes, nodes.len() as u64);
+        for (parent, token) in nodes {
+            write_varint(&mut bytes, *parent as u64);
+            write_varint(&mut bytes, *token as u64);
+        }
+
+        let mut contexts: Vec<&ContextId> = self.chain.keys().collect();
+        contexts.sort_unstable();
+        write_varint(&mut bytes, contexts.len() as u64);
+        for context in contexts {
+            let set = &self.chain[context];
+            write_varint(&mut bytes, *context as u64);
+            write_varint(&mut bytes, set.len() as u64);
+            let mut prev = 0;
+            for (token, count) in set.iter() {
+                write_varint(&mut bytes, (*token - prev) as u64);
+                write_varint(&mut bytes, count as u64);
+                prev = *token;
+            }
+        }
+
+        output.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ModelError> {
+        let mut output = BufWriter::new(File::create(path)?);
+        self.write(&mut output)?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+impl MarkovChain {
+    /// Reads model written by [`MarkovChain::write`], files the chain can't work with
+    /// are rejected with [`ModelError::Corrupted`],
+    /// use [`MarkovChain::with_rand`] to change its random generator
+    pub fn read(mut input: impl Read) -> Result<Self, ModelError> {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        let mut reader = Reader { bytes: &bytes };
+
+        if reader.take(4)? != MODEL_MAGIC {
+            return Err(ModelError::BadMagic);
+        }
+        let version = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+        if version != MODEL_VERSION {
+            return Err(ModelError::UnsupportedVersion(version));
+        }
+        let flags = reader.take(1)?[0];
+        let smoothing = reader.take(1)?[0];
+        let parameter = f64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let fingerprint = Fingerprint(u64::from_le_bytes(reader.take(8)?.try_into().unwrap()));
+
+        let order = reader.usize()?;
+        if order > MAX_ORDER {
+            return Err(ModelError::Corrupted("too big order"));
+        }
+        let mut chain = MarkovChain::with_order(order);
+        chain.smoothing = match smoothing {
+            0 => Smoothing::None,
+            1 => Smoothing::Additive { alpha: parameter },
+            2 => Smoothing::KneserNey,
+            3 => Smoothing::StupidBackoff { alpha: parameter },
+            4 => Smoothing::Katz,
+            x => return Err(ModelError::UnknownSmoothing(x)),
+        };
+        if chain.smoothing.validate().is_err() {
+            return Err(ModelError::Corrupted("smoothing parameter isn't a positive number"));
+        }
+        chain.vocab_fingerprint = (flags & FLAG_FINGERPRINT != 0).then_some(fingerprint);
+        chain.vocab_size = reader.usize()?;
+        if chain.vocab_size > MAX_VOCAB_SIZE {
+            return Err(ModelError::Corrupted("too big vocabulary"));
+        }
+
+        let mut nodes = Vec::new();
+        for _ in 0..reader.usize()? {
+            nodes.push((reader.u32()?, reader.u32()?));
+        }
+        chain.contexts = ContextTrie::from_nodes(&nodes).ok_or(ModelError::Corrupted("invalid context"))?;
+        // Parents precede their children, so depth of a parent is known before its children
+        let mut depths = vec![0; chain.contexts.len()];
+        for (i, (parent, _)) in nodes.iter().enumerate() {
+            depths[i + 1] = depths[*parent as usize] + 1;
+            if depths[i + 1] > chain.order {
+                return Err(ModelError::Corrupted("context is longer than order"));
+            }
+        }
+
+        for _ in 0..reader.usize()? {
+            let context = reader.u32()?;
+            if context as usize >= chain.contexts.len() {
+                return Err(ModelError::Corrupted("unknown context"));
+            }
+            let mut set = WeightedTree::new();
+            let mut token: TokenId = 0;
+            let mut total: usize = 0;
+            for i in 0..reader.usize()? {
+                let delta = reader.u32()?;
+                if i > 0 && delta == 0 {
+                    return Err(ModelError::Corrupted("repeated next token"));
+                }
+                token = token.checked_add(delta).ok_or(ModelError::Corrupted("token overflow"))?;
+                if token as usize >= chain.vocab_size {
+                    return Err(ModelError::Corrupted("token out of vocabulary"));
+                }
+                let count = reader.usize()?;
+                if count == 0 {
+                    return Err(ModelError::Corrupted("zero count"));
+                }
+                total = total.checked_add(count).ok_or(ModelError::Corrupted("count overflow"))?;
+                set.push(count, token);
+            }
+            if set.is_empty() {
+                return Err(ModelError::Corrupted("context without next tokens"));
+            }
+            if chain.chain.insert(context, set).is_some() {
+                return Err(ModelError::Corrupted("repeated context"));
+            }
+        }
+        if !reader.bytes.is_empty() {
+            return Err(ModelError::Corrupted("trailing bytes"));
+        }
+        // Every occurrence is counted after all suffixes of its context, smoothing relies on it
+        for context in chain.chain.keys() {
+            if chain.contexts.parent(*context).is_some_and(|x| !chain.chain.contains_key(&x)) {
+                return Err(ModelError::Corrupted("shorter context has no next tokens"));
+            }
+        }
+
+        chain.kneser_ney = KneserNey::new(&chain);
+        chain.katz = Katz::new(&chain);
+        Ok(chain)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ModelError> {
+        if self.bytes.len() < len {
+            return Err(ModelError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, ModelError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ModelError::Corrupted("too long number"))
+    }
+
+    fn u32(&mut self) -> Result<u32, ModelError> {
+        self.varint()?.try_into().map_err(|_| ModelError::Corrupted("number overflow"))
+    }
+
+    fn usize(&mut self) -> Result<usize, ModelError> {
+        self.varint()?.try_into().map_err(|_| ModelError::Corrupted("number overflow"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizer::VocabularyBuilder;
+
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let vocab = VocabularyBuilder::new()
+            .add_token("a".to_string(), 0)
+            .add_token("b".to_string(), 1)
+            .add_token("c".to_string(), 300)
+            .build();
+        let mut chain = MarkovChain::with_order(2);
+        chain.smoothing = Smoothing::StupidBackoff { alpha: 0.25 };
+        chain.train_chain(&[0, 1, 300, 0, 1, 0, 0, 300, 1]);
+        chain.set_vocabulary(&vocab);
+
+        let mut bytes = Vec::new();
+        chain.write(&mut bytes).unwrap();
+        let loaded = MarkovChain::read(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.order(), 2);
+        assert_eq!(loaded.smoothing, chain.smoothing);
+        assert_eq!(loaded.vocab_size(), 301);
+        assert!(loaded.check_vocabulary(&vocab).is_ok());
+        for context in [&[0, 1][..], &[1, 300], &[5], &[]] {
+            assert_eq!(loaded.distribution(context), chain.distribution(context));
+        }
+
+        let mut again = Vec::new();
+        loaded.write(&mut again).unwrap();
+        assert_eq!(again, bytes);
+
+        let mut kneser_ney = MarkovChain::read(bytes.as_slice()).unwrap();
+        kneser_ney.smoothing = Smoothing::KneserNey;
+        chain.smoothing = Smoothing::KneserNey;
+        assert_eq!(kneser_ney.probability(&[0, 1], 300), chain.probability(&[0, 1], 300));
+    }
+
+    #[test]
+    fn errors() {
+        let mut bytes = Vec::new();
+        MarkovChain::new().write(&mut bytes).unwrap();
+        assert!(MarkovChain::read(bytes.as_slice()).is_ok());
+
+        assert!(matches!(MarkovChain::read(&bytes[..bytes.len() - 1]), Err(ModelError::Truncated)));
+        assert!(matches!(MarkovChain::read(&b"SMDS"[..]), Err(ModelError::BadMagic)));
+
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert!(matches!(MarkovChain::read(future.as_slice()), Err(ModelError::UnsupportedVersion(2))));
+
+        bytes.push(0);
+        assert!(matches!(MarkovChain::read(bytes.as_slice()), Err(ModelError::Corrupted(_))));
+    }
+
+    /// Writes model file of order 2 with vocabulary of 10 tokens
+    fn model(smoothing: u8, parameter: f64, contexts: &[(u64, u64)], chain: &[(u64, &[(u64, u64)])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MODEL_MAGIC);
+        bytes.extend_from_slice(&MODEL_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[0, smoothing]);
+        bytes.extend_from_slice(&parameter.to_le_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        let mut numbers = vec![2, 10, contexts.len() as u64];
+        numbers.extend(contexts.iter().flat_map(|(parent, token)| [*parent, *token]));
+        numbers.push(chain.len() as u64);
+        for (context, next) in chain {
+            numbers.extend([*context, next.len() as u64]);
+            numbers.extend(next.iter().flat_map(|(delta, count)| [*delta, *count]));
+        }
+        for x in numbers {
+            write_varint(&mut bytes, x);
+        }
+        bytes
+    }
+
+    fn corrupted(bytes: Vec<u8>) -> &'static str {
+        match MarkovChain::read(bytes.as_slice()) {
+            Err(ModelError::Corrupted(x)) => x,
+            Err(e) => panic!("unexpected error {e}"),
+            Ok(_) => panic!("model should be rejected"),
+        }
+    }
+
+    #[test]
+    fn validation() {
+        // Contexts "1" and "2 1" with next tokens 3 and 5
+        let contexts = [(0, 1), (1, 2)];
+        let valid = model(1, 0.5, &contexts, &[(0, &[(3, 2), (2, 1)]), (1, &[(3, 2), (2, 1)]), (2, &[(3, 1)])]);
+        let chain = MarkovChain::read(valid.as_slice()).unwrap();
+        assert_eq!(chain.successors(&[2, 1]).unwrap().elements, vec![3]);
+
+        // Context "2 1" has next tokens, but "1" doesn't
+        assert_eq!(corrupted(model(1, 0.5, &contexts, &[(0, &[(3, 1)]), (2, &[(3, 1)])])), "shorter context has no next tokens");
+        assert_eq!(corrupted(model(1, 0.5, &contexts, &[(0, &[(3, 0)])])), "zero count");
+        assert_eq!(corrupted(model(1, 0.5, &contexts, &[(0, &[(3, 1), (0, 1)])])), "repeated next token");
+        assert_eq!(corrupted(model(1, 0.5, &contexts, &[(0, &[(3, 1)]), (0, &[(4, 1)])])), "repeated context");
+        assert_eq!(corrupted(model(1, 0.5, &contexts, &[(0, &[])])), "context without next tokens");
+        assert_eq!(corrupted(model(1, 0.5, &[(0, 1), (1, 2), (2, 3)], &[])), "context is longer than order");
+        for alpha in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(corrupted(model(3, alpha, &contexts, &[])).starts_with("smoothing parameter"));
+        }
+        assert!(MarkovChain::read(model(2, f64::NAN, &contexts, &[]).as_slice()).is_ok());
+        assert_eq!(MarkovChain::read(model(4, 0.0, &contexts, &[]).as_slice()).unwrap().smoothing, Smoothing::Katz);
+
+        let mut huge = model(0, 0.0, &[], &[]);
+        huge.truncate(24);
+        write_varint(&mut huge, MAX_ORDER as u64 + 1);
+        huge.extend_from_slice(&[10, 0, 0]);
+        assert_eq!(corrupted(huge.clone()), "too big order");
+        huge.truncate(24);
+        write_varint(&mut huge, 2);
+        write_varint(&mut huge, u64::MAX);
+        huge.extend_from_slice(&[0, 0]);
+        assert_eq!(corrupted(huge), "too big vocabulary");
+    }
+}
@@ -0,0 +1,180 @@
+/// Transformation of next token probabilities before a token is chosen.
+///
+/// Filters are applied in order top-k, typical, top-p, min-p, every one to what
+/// the previous ones kept, then temperature reshapes the kept probabilities.
+/// Default sampling chooses proportionally to probabilities.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    /// Probabilities are raised to the power `1 / temperature`, lower temperatures
+    /// make text more predictable, zero always chooses the most probable token
+    pub temperature: f64,
+
+    /// Keeps only this count of the most probable tokens, zero keeps all
+    pub top_k: usize,
+
+    /// Nucleus sampling, keeps the most probable tokens whose probabilities sum up to `top_p`
+    pub top_p: f64,
+
+    /// Drops tokens less probable than `min_p` times probability of the most probable token
+    pub min_p: f64,
+
+    /// Locally typical sampling, keeps tokens whose information content is the closest
+    /// to entropy of the distribution until their probabilities sum up to `typical_p`
+    pub typical_p: f64,
+}
+
+impl Sampling {
+    /// Sampling which always chooses the most probable token
+    pub fn greedy() -> Self {
+        Self {
+            temperature: 0.0,
+            ..Self::default()
+        }
+    }
+
+    /// Checks that parameters are in their ranges
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.temperature >= 0.0 && self.temperature.is_finite()) {
+            return Err(format!("temperature should be a non-negative number, got {}", self.temperature));
+        }
+        for (name, value) in [("top-p", self.top_p), ("min-p", self.min_p), ("typical-p", self.typical_p)] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{name} should be between 0 and 1, got {value}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts probabilities of tokens into weights tokens are chosen with,
+    /// probabilities don't have to be normalized
+    pub fn apply(&self, distribution: &mut [f64]) {
+        let mut order: Vec<usize> = (0..distribution.len()).filter(|i| distribution[*i] > 0.0).collect();
+        if order.is_empty() {
+            return;
+        }
+        // Most probable first, ties are broken by lower `TokenId`
+        order.sort_by(|a, b| distribution[*b].total_cmp(&distribution[*a]).then(a.cmp(b)));
+
+        if self.top_k > 0 {
+            for i in order.drain(self.top_k.min(order.len())..) {
+                distribution[i] = 0.0;
+            }
+        }
+
+        if self.typical_p < 1.0 {
+            let total: f64 = order.iter().map(|i| distribution[*i]).sum();
+            let entropy: f64 = order.iter().map(|i| distribution[*i] / total).map(|p| -p * p.ln()).sum();
+            let deviation = |i: usize| (-(distribution[i] / total).ln() - entropy).abs();
+            let mut typical = order.clone();
+            typical.sort_by(|a, b| deviation(*a).total_cmp(&deviation(*b)).then(a.cmp(b)));
+            keep_mass(distribution, &typical, self.typical_p);
+            order.retain(|i| distribution[*i] > 0.0);
+        }
+
+        if self.top_p < 1.0 {
+            keep_mass(distribution, &order, self.top_p);
+            order.retain(|i| distribution[*i] > 0.0);
+        }
+
+        let max = distribution[order[0]];
+        if self.min_p > 0.0 {
+            for i in order.iter() {
+                if distribution[*i] < self.min_p * max {
+                    distribution[*i] = 0.0;
+                }
+            }
+        }
+
+        if self.temperature == 0.0 {
+            for i in order[1..].iter() {
+                distribution[*i] = 0.0;
+            }
+        } else if self.temperature != 1.0 {
+            // Relative to the maximum, so low temperatures don't underflow all weights
+            for x in distribution.iter_mut().filter(|x| **x > 0.0) {
+                *x = (*x / max).powf(1.0 / self.temperature);
+            }
+        }
+    }
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            temperature: 1.0,
+            top_k: 0,
+            top_p: 1.0,
+            min_p: 0.0,
+            typical_p: 1.0,
+        }
+    }
+}
+
+/// Keeps tokens in given order until their probabilities sum up to `mass`
+/// of the total, zeroes the rest, at least one token is kept
+fn keep_mass(distribution: &mut [f64], order: &[usize], mass: f64) {
+    let total: f64 = order.iter().map(|i| distribution[*i]).sum();
+    let mut sum = 0.0;
+    for (n, i) in order.iter().enumerate() {
+        if n > 0 && sum >= mass * total {
+            distribution[*i] = 0.0;
+        } else {
+            sum += distribution[*i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(sampling: Sampling, distribution: &[f64]) -> Vec<f64> {
+        let mut distribution = distribution.to_vec();
+        sampling.apply(&mut distribution);
+        let total: f64 = distribution.iter().sum();
+        distribution.iter().map(|x| x / total).collect()
+    }
+
+    fn assert_close(a: Vec<f64>, b: Vec<f64>) {
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-12), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn filters() {
+        let distribution = [0.1, 0.4, 0.0, 0.3, 0.2];
+        assert_close(apply(Sampling::default(), &distribution), distribution.to_vec());
+        assert_close(apply(Sampling::greedy(), &distribution), vec![0.0, 1.0, 0.0, 0.0, 0.0]);
+
+        let top_k = Sampling { top_k: 2, ..Sampling::default() };
+        assert_close(apply(top_k, &distribution), vec![0.0, 4.0 / 7.0, 0.0, 3.0 / 7.0, 0.0]);
+
+        let top_p = Sampling { top_p: 0.8, ..Sampling::default() };
+        assert_close(apply(top_p, &distribution), vec![0.0, 4.0 / 9.0, 0.0, 3.0 / 9.0, 2.0 / 9.0]);
+        let top_p = Sampling { top_p: 0.0, ..Sampling::default() };
+        assert_close(apply(top_p, &distribution), vec![0.0, 1.0, 0.0, 0.0, 0.0]);
+
+        let min_p = Sampling { min_p: 0.5, ..Sampling::default() };
+        assert_close(apply(min_p, &distribution), vec![0.0, 4.0 / 9.0, 0.0, 3.0 / 9.0, 2.0 / 9.0]);
+
+        // Entropy is 1.28 nats, information content of 0.3 is 1.20 and of 0.2 is 1.61
+        let typical = Sampling { typical_p: 0.5, ..Sampling::default() };
+        assert_close(apply(typical, &distribution), vec![0.0, 0.0, 0.0, 0.6, 0.4]);
+    }
+
+    #[test]
+    fn temperature() {
+        let distribution = [0.2, 0.8];
+        let hot = apply(Sampling { temperature: 2.0, ..Sampling::default() }, &distribution);
+        assert!((hot[0] - 1.0 / 3.0).abs() < 1e-12);
+        let cold = apply(Sampling { temperature: 0.5, ..Sampling::default() }, &distribution);
+        assert!((cold[0] - 1.0 / 17.0).abs() < 1e-12);
+        let frozen = apply(Sampling { temperature: 1e-3, ..Sampling::default() }, &distribution);
+        assert_eq!(frozen, vec![0.0, 1.0]);
+
+        // Ties are broken by lower id
+        assert_eq!(apply(Sampling::greedy(), &[0.5, 0.5]), vec![1.0, 0.0]);
+        assert!(Sampling { top_p: 1.5, ..Sampling::default() }.validate().is_err());
+        assert!(Sampling { temperature: -1.0, ..Sampling::default() }.validate().is_err());
+        assert!(Sampling::greedy().validate().is_ok());
+    }
+}
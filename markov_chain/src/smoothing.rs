@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use tokenizer::tokenizers::TokenId;
+
+use crate::{count, weighted, ContextId, MarkovChain, WeightedTree};
+
+/// Discounts used when some count of counts is zero, e.g. for tiny corpora
+const DEFAULT_DISCOUNTS: [f64; 3] = [0.5, 1.0, 1.5];
+
+/// Minimal discount, keeps some probability mass for unseen tokens
+const MIN_DISCOUNT: f64 = 0.1;
+
+/// Counts up to this one are discounted by Katz smoothing, bigger ones are considered reliable
+const KATZ_MAX_COUNT: usize = 5;
+
+/// Estimation of next token probabilities from counts
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Smoothing {
+    /// Relative counts after the full context, generation stops
+    /// after contexts unseen in training
+    #[default]
+    None,
+
+    /// Adds `alpha` to count of every token of the vocabulary after the full context,
+    /// unseen contexts have uniform distribution
+    Additive { alpha: f64 },
+
+    /// Interpolated modified Kneser-Ney smoothing: counts 1, 2 and 3+ are discounted
+    /// and discounted mass goes to the distribution of the shorter context.
+    /// Shorter contexts count distinct tokens preceding them instead of occurrences.
+    /// Discounts are estimated from counts of counts for every context length.
+    KneserNey,
+
+    /// Relative count after the longest context where the token was seen,
+    /// multiplied by `alpha` for every shorter context, normalized over the vocabulary
+    StupidBackoff { alpha: f64 },
+
+    /// Katz backoff: counts up to 5 after the longest seen context are discounted
+    /// with Good-Turing estimates and discounted mass goes to tokens unseen after it,
+    /// proportionally to their probabilities after the shorter context.
+    /// Discounts are estimated from counts of counts for every context length.
+    Katz,
+}
+
+impl Smoothing {
+    /// Checks that parameter is a positive number, zero `alpha` leaves
+    /// no probability for tokens after unseen contexts
+    pub fn validate(&self) -> Result<(), String> {
+        let alpha = match *self {
+            Smoothing::Additive { alpha } | Smoothing::StupidBackoff { alpha } => alpha,
+            _ => return Ok(()),
+        };
+        if !(alpha > 0.0 && alpha.is_finite()) {
+            return Err(format!("smoothing parameter should be a positive number, got {alpha}"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Smoothing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Smoothing::None => write!(f, "none"),
+            Smoothing::Additive { alpha } => write!(f, "additive {alpha}"),
+            Smoothing::KneserNey => write!(f, "kneser-ney"),
+            Smoothing::StupidBackoff { alpha } => write!(f, "stupid-backoff {alpha}"),
+            Smoothing::Katz => write!(f, "katz"),
+        }
+    }
+}
+
+impl FromStr for Smoothing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, alpha) = match s.split_once(' ') {
+            Some((name, alpha)) => {
+                let alpha: f64 = alpha.parse().map_err(|_| format!("invalid smoothing parameter {alpha}"))?;
+                (name, Some(alpha))
+            },
+            None => (s, None),
+        };
+
+        let smoothing = match (name, alpha) {
+            ("none", None) => Smoothing::None,
+            ("additive", alpha) => Smoothing::Additive { alpha: alpha.unwrap_or(1.0) },
+            ("kneser-ney", None) => Smoothing::KneserNey,
+            ("stupid-backoff", alpha) => Smoothing::StupidBackoff { alpha: alpha.unwrap_or(0.4) },
+            ("katz", None) => Smoothing::Katz,
+            ("none" | "kneser-ney" | "katz", Some(_)) => return Err(format!("smoothing {name} has no parameter")),
+            _ => return Err(format!(
+                "unknown smoothing {s}, expected none, \"additive [alpha]\", kneser-ney, \"stupid-backoff [alpha]\" or katz"
+            )),
+        };
+        smoothing.validate()?;
+        Ok(smoothing)
+    }
+}
+
+/// Statistics of Kneser-Ney smoothing computed from counts of the chain
+#[derive(Debug, Clone, Default)]
+pub(crate) struct KneserNey {
+    /// Context -> next tokens weighted by count of distinct tokens preceding
+    /// the context and the next token, sorted by `TokenId`
+    continuations: HashMap<ContextId, WeightedTree<TokenId>>,
+
+    /// Discounts of counts 1, 2 and 3+ for every context length
+    discounts: Vec<[f64; 3]>,
+
+    /// Context -> share of probability mass given to the shorter context
+    backoff: HashMap<ContextId, f64>,
+}
+
+impl KneserNey {
+    pub(crate) fn new<R>(chain: &MarkovChain<R>) -> Self {
+        let mut continuations: HashMap<ContextId, HashMap<TokenId, usize>> = HashMap::new();
+        for (context, set) in chain.chain.iter() {
+            if let Some(parent) = chain.contexts.parent(*context) {
+                let counts = continuations.entry(parent).or_default();
+                for token in set.elements.iter() {
+                    *counts.entry(*token).or_default() += 1;
+                }
+            }
+        }
+        let mut result = Self {
+            continuations: continuations.into_iter().map(|(context, x)| (context, weighted(x))).collect(),
+            discounts: Vec::new(),
+            backoff: HashMap::new(),
+        };
+
+        // Contexts with continuation counts need backoff even if they have no next tokens themselves
+        let contexts = chain.chain.keys().chain(result.continuations.keys());
+        let depths: HashMap<ContextId, usize> = contexts.map(|x| (*x, chain.contexts.depth(*x))).collect();
+
+        // Counts of counts 1, 2, 3 and 4 for every context length
+        let mut counts_of_counts = vec![[0; 4]; chain.order + 1];
+        for (context, depth) in depths.iter() {
+            if let Some(set) = result.counts(chain, *context, *depth) {
+                for (_, count) in set.iter() {
+                    if (1..=4).contains(&count) {
+                        counts_of_counts[*depth][count - 1] += 1;
+                    }
+                }
+            }
+        }
+        result.discounts = counts_of_counts.into_iter().map(estimate_discounts).collect();
+
+        for (context, depth) in depths {
+            if let Some(set) = result.counts(chain, context, depth) {
+                let discounted: f64 = set.iter().map(|(_, count)| result.discount(depth, count)).sum();
+                result.backoff.insert(context, discounted / set.total() as f64);
+            }
+        }
+        result
+    }
+
+    /// Returns counts used for context of given length: occurrences
+    /// for the full context, continuation counts for shorter ones
+    fn counts<'a, R>(&'a self, chain: &'a MarkovChain<R>, context: ContextId, depth: usize) -> Option<&'a WeightedTree<TokenId>> {
+        if depth == chain.order {
+            chain.chain.get(&context)
+        } else {
+            self.continuations.get(&context)
+        }
+    }
+
+    /// Returns share of probability mass given to the shorter context
+    fn backoff(&self, context: ContextId) -> f64 {
+        self.backoff.get(&context).copied().unwrap_or(0.0)
+    }
+
+    fn discount(&self, depth: usize, count: usize) -> f64 {
+        let discounts = self.discounts[depth];
+        match count {
+            0 => 0.0,
+            1 => discounts[0],
+            2 => discounts[1],
+            _ => discounts[2],
+        }
+    }
+}
+
+/// Estimates discounts of counts 1, 2 and 3+ from counts of counts 1..=4
+/// as proposed by Chen and Goodman
+fn estimate_discounts(counts_of_counts: [usize; 4]) -> [f64; 3] {
+    if counts_of_counts.contains(&0) {
+        return DEFAULT_DISCOUNTS;
+    }
+    let [n1, n2, n3, n4] = counts_of_counts.map(|x| x as f64);
+    let y = n1 / (n1 + 2.0 * n2);
+    let discounts = [1.0 - 2.0 * y * n2 / n1, 2.0 - 3.0 * y * n3 / n2, 3.0 - 4.0 * y * n4 / n3];
+    [0, 1, 2].map(|i| discounts[i].clamp(MIN_DISCOUNT, i as f64 + 1.0))
+}
+
+/// Good-Turing discounts of Katz smoothing computed from counts of the chain
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Katz {
+    /// Ratios of discounted to original counts 1..=5 for every context length
+    discounts: Vec<[f64; KATZ_MAX_COUNT]>,
+}
+
+impl Katz {
+    pub(crate) fn new<R>(chain: &MarkovChain<R>) -> Self {
+        // Counts of counts 1..=6 for every context length
+        let mut counts_of_counts = vec![[0; KATZ_MAX_COUNT + 1]; chain.order + 1];
+        for (context, set) in chain.chain.iter() {
+            let depth = chain.contexts.depth(*context);
+            for (_, count) in set.iter() {
+                if (1..=KATZ_MAX_COUNT + 1).contains(&count) {
+                    counts_of_counts[depth][count - 1] += 1;
+                }
+            }
+        }
+        Self {
+            discounts: counts_of_counts.into_iter().map(estimate_katz_discounts).collect(),
+        }
+    }
+
+    /// Returns discounted count
+    fn discounted(&self, depth: usize, count: usize) -> f64 {
+        match count {
+            1..=KATZ_MAX_COUNT => self.discounts[depth][count - 1] * count as f64,
+            _ => count as f64,
+        }
+    }
+}
+
+/// Estimates Good-Turing discounts of counts 1..=5 from counts of counts 1..=6
+/// as proposed by Katz, half of a count is discounted when some count of counts
+/// is zero or estimates don't decrease counts
+fn estimate_katz_discounts(counts_of_counts: [usize; KATZ_MAX_COUNT + 1]) -> [f64; KATZ_MAX_COUNT] {
+    let fallback = std::array::from_fn(|i| 1.0 - 0.5 / (i + 1) as f64);
+    if counts_of_counts.contains(&0) {
+        return fallback;
+    }
+    let n = counts_of_counts.map(|x| x as f64);
+    let max = KATZ_MAX_COUNT as f64;
+    // Share of the count which would be discounted from counts above the maximal one
+    let common = (max + 1.0) * n[KATZ_MAX_COUNT] / n[0];
+    let discounts: [f64; KATZ_MAX_COUNT] = std::array::from_fn(|i| {
+        let count = (i + 1) as f64;
+        let good_turing = (count + 1.0) * n[i + 1] / n[i];
+        (good_turing / count - common) / (1.0 - common)
+    });
+    match discounts.iter().all(|x| *x > 0.0 && *x < 1.0) {
+        true => discounts,
+        false => fallback,
+    }
+}
+
+impl<R> MarkovChain<R> {
+    /// Returns probability of token after the last `order` tokens of the context.
+    ///
+    /// Probabilities of stupid backoff and Katz smoothing are normalized over the whole vocabulary,
+    /// so computing one of them costs as much as [`MarkovChain::distribution`].
+    pub fn probability(&self, context: &[TokenId], token: TokenId) -> f64 {
+        if token as usize >= self.vocab_size {
+            return 0.0;
+        }
+
+        match self.smoothing {
+            Smoothing::None => match self.successors(context) {
+                Some(set) => count(set, token) as f64 / set.total() as f64,
+                None => 0.0,
+            },
+            Smoothing::Additive { alpha } => {
+                let (count, total) = match self.successors(context) {
+                    Some(set) => (count(set, token), set.total()),
+                    None => (0, 0),
+                };
+                (count as f64 + alpha) / (total as f64 + alpha * self.vocab_size as f64)
+            },
+            Smoothing::KneserNey => {
+                let mut probability = 1.0 / self.vocab_size as f64;
+                for (depth, context) in self.suffixes(context).into_iter().enumerate() {
+                    if let Some(set) = self.kneser_ney.counts(self, context, depth) {
+                        let count = count(set, token);
+                        let discounted = count as f64 - self.kneser_ney.discount(depth, count);
+                        probability = discounted / set.total() as f64 + self.kneser_ney.backoff(context) * probability;
+                    }
+                }
+                probability
+            },
+            Smoothing::StupidBackoff { .. } | Smoothing::Katz => self.distribution(context)[token as usize],
+        }
+    }
+
+    /// Returns probabilities of all `TokenId`s below [`MarkovChain::vocab_size`]
+    /// after the last `order` tokens of the context.
+    ///
+    /// Without smoothing all probabilities are zero after contexts unseen in training.
+    pub fn distribution(&self, context: &[TokenId]) -> Vec<f64> {
+        let size = self.vocab_size;
+        let mut distribution = vec![0.0; size];
+        if size == 0 {
+            return distribution;
+        }
+
+        match self.smoothing {
+            Smoothing::None => {
+                if let Some(set) = self.successors(context) {
+                    add_counts(&mut distribution, set, |count| count as f64 / set.total() as f64);
+                }
+            },
+            Smoothing::Additive { alpha } => {
+                let total = self.successors(context).map_or(0, |x| x.total()) as f64 + alpha * size as f64;
+                distribution.fill(alpha / total);
+                if let Some(set) = self.successors(context) {
+                    add_counts(&mut distribution, set, |count| count as f64 / total);
+                }
+            },
+            Smoothing::KneserNey => {
+                distribution.fill(1.0 / size as f64);
+                for (depth, context) in self.suffixes(context).into_iter().enumerate() {
+                    if let Some(set) = self.kneser_ney.counts(self, context, depth) {
+                        let backoff = self.kneser_ney.backoff(context);
+                        distribution.iter_mut().for_each(|x| *x *= backoff);
+                        add_counts(&mut distribution, set, |count| {
+                            (count as f64 - self.kneser_ney.discount(depth, count)) / set.total() as f64
+                        });
+                    }
+                }
+            },
+            Smoothing::StupidBackoff { alpha } => {
+                distribution.fill(1.0 / size as f64);
+                for context in self.suffixes(context) {
+                    if let Some(set) = self.chain.get(&context) {
+                        distribution.iter_mut().for_each(|x| *x *= alpha);
+                        for (token, count) in set.iter() {
+                            if let Some(x) = distribution.get_mut(*token as usize) {
+                                *x = count as f64 / set.total() as f64;
+                            }
+                        }
+                    }
+                }
+                let total: f64 = distribution.iter().sum();
+                distribution.iter_mut().for_each(|x| *x /= total);
+            },
+            Smoothing::Katz => {
+                distribution.fill(1.0 / size as f64);
+                for (depth, context) in self.suffixes(context).into_iter().enumerate() {
+                    if let Some(set) = self.chain.get(&context) {
+                        let total = set.total() as f64;
+                        let discounted: f64 = set.iter().map(|(_, count)| self.katz.discounted(depth, count)).sum();
+                        let left = 1.0 - discounted / total;
+                        let seen: f64 = set.elements.iter().filter_map(|x| distribution.get(*x as usize)).sum();
+                        let unseen = distribution.iter().sum::<f64>() - seen;
+                        // Mass left after discounting is shared by unseen tokens
+                        let scale = if unseen > 0.0 { left / unseen } else { 0.0 };
+                        distribution.iter_mut().for_each(|x| *x *= scale);
+                        for (token, count) in set.iter() {
+                            if let Some(x) = distribution.get_mut(*token as usize) {
+                                *x = self.katz.discounted(depth, count) / total;
+                            }
+                        }
+                    }
+                }
+                // Mass left after contexts followed by every token is lost
+                let total: f64 = distribution.iter().sum();
+                distribution.iter_mut().for_each(|x| *x /= total);
+            },
+        }
+        distribution
+    }
+
+    /// Returns ids of known suffixes of the last `order` tokens of the context,
+    /// from the empty context to the longest one
+    fn suffixes(&self, context: &[TokenId]) -> Vec<ContextId> {
+        self.contexts.suffixes(&context[context.len().saturating_sub(self.order)..])
+    }
+}
+
+/// Adds value of every count of next tokens to their probabilities
+fn add_counts(distribution: &mut [f64], set: &WeightedTree<TokenId>, value: impl Fn(usize) -> f64) {
+    for (token, count) in set.iter() {
+        if let Some(x) = distribution.get_mut(*token as usize) {
+            *x += value(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("additive 0.5".parse(), Ok(Smoothing::Additive { alpha: 0.5 }));
+        assert_eq!("stupid-backoff".parse(), Ok(Smoothing::StupidBackoff { alpha: 0.4 }));
+        assert_eq!("kneser-ney".parse(), Ok(Smoothing::KneserNey));
+        assert_eq!(Smoothing::Additive { alpha: 1.0 }.to_string(), "additive 1");
+        assert!("additive -1".parse::<Smoothing>().is_err());
+        assert!("kneser-ney 2".parse::<Smoothing>().is_err());
+        assert_eq!("katz".parse(), Ok(Smoothing::Katz));
+        assert_eq!(Smoothing::Katz.to_string(), "katz");
+        assert!("katz 0.5".parse::<Smoothing>().is_err());
+        assert!("katz-backoff".parse::<Smoothing>().is_err());
+        assert!("stupid-backoff nan".parse::<Smoothing>().is_err());
+        assert!(Smoothing::Additive { alpha: 0.0 }.validate().is_err());
+        assert!(Smoothing::StupidBackoff { alpha: f64::INFINITY }.validate().is_err());
+        assert!(Smoothing::KneserNey.validate().is_ok());
+    }
+
+    #[test]
+    fn distributions() {
+        let mut chain = MarkovChain::with_order(2);
+        chain.train_chain(&[1, 2, 3, 1, 2, 3, 4, 2, 5, 1, 2, 4, 0, 2, 3]);
+        assert_eq!(chain.vocab_size(), 6);
+
+        let smoothings = [
+            Smoothing::Additive { alpha: 0.5 },
+            Smoothing::KneserNey,
+            Smoothing::StupidBackoff { alpha: 0.4 },
+            Smoothing::Katz,
+        ];
+        for smoothing in smoothings {
+            chain.smoothing = smoothing;
+            // Seen, unseen, short and empty contexts
+            for context in [&[1, 2][..], &[5, 5], &[2], &[]] {
+                let distribution = chain.distribution(context);
+                assert!((distribution.iter().sum::<f64>() - 1.0).abs() < 1e-9, "{smoothing} {context:?}");
+                for (token, probability) in distribution.iter().enumerate() {
+                    assert!(*probability > 0.0);
+                    assert!((chain.probability(context, token as TokenId) - probability).abs() < 1e-12);
+                }
+            }
+            assert!(chain.probability(&[1, 2], 3) > chain.probability(&[1, 2], 5));
+        }
+
+        chain.smoothing = Smoothing::None;
+        assert_eq!(chain.probability(&[1, 2], 3), 2.0 / 3.0);
+        assert_eq!(chain.distribution(&[5, 5]), vec![0.0; 6]);
+    }
+
+    #[test]
+    fn kneser_ney_backoff() {
+        let mut chain = MarkovChain::with_order(1);
+        chain.smoothing = Smoothing::KneserNey;
+        // "1" is frequent but follows only 0, "2" follows many tokens
+        chain.train_chain(&[0, 1, 0, 1, 0, 1, 0, 1, 3, 2, 4, 2, 5, 2]);
+
+        // Context 6 is unseen, continuation counts prefer 2
+        chain.vocab_size = 7;
+        assert!(chain.probability(&[6], 2) > chain.probability(&[6], 1));
+        assert!(chain.probability(&[0], 1) > 0.5);
+    }
+
+    #[test]
+    fn katz() {
+        let mut chain = MarkovChain::with_order(1);
+        chain.smoothing = Smoothing::Katz;
+        chain.train_chain(&[0, 1, 0, 1, 0, 2]);
+        let assert_close = |context: &[TokenId], expected: [f64; 3]| {
+            let distribution = chain.distribution(context);
+            assert!(distribution.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-12), "{distribution:?}");
+        };
+
+        // Counts of counts are too small for Good-Turing estimates, so half of every count is discounted.
+        // Every token follows the empty context, so its discounted counts are normalized
+        assert_close(&[], [5.0 / 9.0, 3.0 / 9.0, 1.0 / 9.0]);
+
+        // After "1" only 0 was seen twice, the rest 1/4 is shared by 1 and 2 like after the empty context
+        assert_close(&[1], [0.75, 0.1875, 0.0625]);
+        assert_eq!(chain.probability(&[1], 2), chain.distribution(&[1])[2]);
+
+        assert_eq!(estimate_katz_discounts([0, 1, 1, 1, 1, 1])[1], 0.75);
+        let discounts = estimate_katz_discounts([400, 150, 80, 50, 30, 20]);
+        assert!(discounts.iter().all(|x| *x > 0.0 && *x < 1.0), "{discounts:?}");
+        // Counts above 5 aren't discounted
+        assert_eq!(chain.katz.discounted(0, 6), 6.0);
+    }
+}
@@ -1,51 +1,176 @@
-use std::ops::Range;
+use rand::Rng;
 
 /// `Vec` whose elements have weight.
 ///
-/// You can choose an element from `WeightedVec` depends on their weight.
+/// You can choose an element from `WeightedVec` depends on their weight,
+/// element `i` occupies numbers from the sum of weights before it up to the sum including it.
 #[derive(Debug, Clone)]
 pub struct WeightedVec<T> {
     pub elements: Vec<T>,
-    pub ranges: Vec<Range<usize>>,
-    pub next: usize,
+
+    /// Sum of weights of elements up to and including `i`-th
+    cumulative: Vec<usize>,
 }
 
 impl<T> WeightedVec<T> {
     pub fn new() -> Self {
         Self {
             elements: Vec::new(),
-            ranges: Vec::new(),
-            next: 0,
+            cumulative: Vec::new(),
         }
     }
 
     /// Appends an element and his weight
     pub fn push(&mut self, weight: usize, element: T) {
         self.elements.push(element);
-        self.ranges.push(self.next..self.next+weight);
-        self.next += weight;
+        self.cumulative.push(self.total() + weight);
     }
 
     /// Chooses an element from collections depends on weights of elements.
     ///
-    /// Accepts `num` in range 0..len, where len is collection's lenght
+    /// Accepts `num` in range 0..total, where total is sum of weights,
+    /// takes O(log n) time
     pub fn choose(&self, num: usize) -> Option<&T> {
-        for (i, range) in self.ranges.iter().enumerate() {
-            if range.contains(&num) {
-                return self.elements.get(i);
-            }
+        let index = self.cumulative.partition_point(|end| *end <= num);
+        self.elements.get(index)
+    }
+
+    /// Chooses an element with probability proportional to its weight,
+    /// `None` if all weights are zero
+    pub fn sample(&self, rand: &mut impl Rng) -> Option<&T> {
+        match self.total() {
+            0 => None,
+            total => self.choose(rand.gen_range(0..total)),
         }
-        None
     }
-    
+
+    /// Returns weight of `index`-th element
+    pub fn weight(&self, index: usize) -> usize {
+        match index {
+            0 => self.cumulative[0],
+            _ => self.cumulative[index] - self.cumulative[index - 1],
+        }
+    }
+
+    /// Returns elements with their weights
+    pub fn iter(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.elements.iter().enumerate().map(|(i, x)| (x, self.weight(i)))
+    }
+
+    /// Returns sum of weights of all elements
+    pub fn total(&self) -> usize {
+        self.cumulative.last().copied().unwrap_or(0)
+    }
+
     /// Returns count of collection's elements
     pub fn len(&self) -> usize {
         self.elements.len()
     }
+
+    /// Returns `true` if collection has no elements
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+}
+
+impl<T> Default for WeightedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Weighted elements prepared for choosing in O(1) time with Vose's alias method.
+///
+/// Every element owns a column of `total` numbers, where total is sum of weights.
+/// A column is split between its element and one alias element,
+/// so an element owns as many numbers in all columns as its weight times count of elements.
+/// Unlike [`WeightedVec`] it can't be extended, build a new table instead.
+#[derive(Debug, Clone)]
+pub struct AliasTable<T> {
+    elements: Vec<T>,
+
+    /// Count of numbers of column owned by its element, the rest is owned by the alias
+    thresholds: Vec<usize>,
+    aliases: Vec<usize>,
+    total: usize,
+}
+
+impl<T> AliasTable<T> {
+    /// Chooses an element from the table depends on weights of elements.
+    ///
+    /// Accepts `num` in range 0..len*total, where len is count of elements
+    /// and total is sum of their weights
+    pub fn choose(&self, num: usize) -> Option<&T> {
+        if self.total == 0 {
+            return None;
+        }
+        let column = num / self.total;
+        let index = match num % self.total < *self.thresholds.get(column)? {
+            true => column,
+            false => self.aliases[column],
+        };
+        self.elements.get(index)
+    }
+
+    /// Chooses an element with probability proportional to its weight,
+    /// `None` if all weights are zero
+    pub fn sample(&self, rand: &mut impl Rng) -> Option<&T> {
+        if self.total == 0 {
+            return None;
+        }
+        let column = rand.gen_range(0..self.elements.len());
+        self.choose(column * self.total + rand.gen_range(0..self.total))
+    }
+
+    /// Returns sum of weights of all elements
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns count of table's elements
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Returns `true` if table has no elements
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+}
+
+impl<T> From<WeightedVec<T>> for AliasTable<T> {
+    fn from(value: WeightedVec<T>) -> Self {
+        let len = value.len();
+        let total = value.total();
+        // Weights scaled so that a column holds exactly `total`
+        let mut scaled: Vec<usize> = (0..len).map(|i| value.weight(i) * len).collect();
+        let mut thresholds = vec![total; len];
+        let mut aliases: Vec<usize> = (0..len).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..len).partition(|i| scaled[*i] < total);
+        while let (Some(less), Some(more)) = (small.pop(), large.pop()) {
+            thresholds[less] = scaled[less];
+            aliases[less] = more;
+            scaled[more] -= total - scaled[less];
+            match scaled[more] < total {
+                true => small.push(more),
+                false => large.push(more),
+            }
+        }
+
+        Self {
+            elements: value.elements,
+            thresholds,
+            aliases,
+            total,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
     use super::*;
 
     #[test]
@@ -63,4 +188,42 @@ mod tests {
         assert_eq!(list.choose(29), Some(&2));
         assert_eq!(list.choose(30), None);
     }
+
+    #[test]
+    fn zero_weights() {
+        let mut list: WeightedVec<usize> = WeightedVec::new();
+        list.push(0, 0);
+        list.push(2, 1);
+        list.push(0, 2);
+        list.push(1, 3);
+
+        assert_eq!(list.choose(0), Some(&1));
+        assert_eq!(list.choose(2), Some(&3));
+        assert_eq!(list.total(), 3);
+        assert_eq!(list.iter().map(|(_, weight)| weight).collect::<Vec<_>>(), vec![0, 2, 0, 1]);
+
+        assert_eq!(WeightedVec::<usize>::new().sample(&mut StdRng::seed_from_u64(0)), None);
+    }
+
+    #[test]
+    fn alias() {
+        let weights = [3, 0, 7, 1, 1, 12];
+        let mut list = WeightedVec::new();
+        for (i, weight) in weights.iter().enumerate() {
+            list.push(*weight, i);
+        }
+        let table = AliasTable::from(list);
+
+        // Every element owns exactly its weight times count of elements numbers
+        let mut counts = [0; 6];
+        for num in 0..table.len() * table.total() {
+            counts[*table.choose(num).unwrap()] += 1;
+        }
+        assert_eq!(counts, weights.map(|x| x * weights.len()));
+        assert_eq!(table.choose(table.len() * table.total()), None);
+
+        let mut rand = StdRng::seed_from_u64(0);
+        assert!((0..100).all(|_| table.sample(&mut rand) != Some(&1)));
+        assert_eq!(AliasTable::from(WeightedVec::<usize>::new()).sample(&mut rand), None);
+    }
 }
@@ -0,0 +1,256 @@
+use rand::Rng;
+
+use crate::WeightedVec;
+
+/// Weighted elements whose weights can change, backed by a Fenwick tree.
+///
+/// Like [`WeightedVec`], element `i` occupies numbers from the sum of weights before it
+/// up to the sum including it, but weights can be increased, decreased and elements removed.
+/// Choosing an element and every update take O(log n) time.
+#[derive(Debug, Clone)]
+pub struct WeightedTree<T> {
+    pub elements: Vec<T>,
+
+    /// `i`-th node holds sum of weights of elements from `i + 1 - lowbit(i + 1)` to `i`
+    tree: Vec<usize>,
+}
+
+impl<T> WeightedTree<T> {
+    pub fn new() -> Self {
+        Self {
+            elements: Vec::new(),
+            tree: Vec::new(),
+        }
+    }
+
+    /// Creates tree from elements and their weights in O(n) time
+    pub fn from_weights(elements: Vec<T>, weights: Vec<usize>) -> Self {
+        assert_eq!(elements.len(), weights.len(), "every element needs a weight");
+        let mut tree = weights;
+        for i in 1..=tree.len() {
+            let parent = i + lowbit(i);
+            if parent <= tree.len() {
+                tree[parent - 1] += tree[i - 1];
+            }
+        }
+        Self { elements, tree }
+    }
+
+    /// Appends an element and his weight
+    pub fn push(&mut self, weight: usize, element: T) {
+        let i = self.tree.len() + 1;
+        // The new node covers itself and previous elements it's responsible for
+        let node = weight + self.prefix(i - 1) - self.prefix(i - lowbit(i));
+        self.elements.push(element);
+        self.tree.push(node);
+    }
+
+    /// Chooses an element from collections depends on weights of elements.
+    ///
+    /// Accepts `num` in range 0..total, where total is sum of weights
+    pub fn choose(&self, num: usize) -> Option<&T> {
+        let mut position = 0;
+        let mut rest = num;
+        let mut step = match self.tree.len() {
+            0 => 0,
+            len => 1 << len.ilog2(),
+        };
+        while step > 0 {
+            if position + step <= self.tree.len() && self.tree[position + step - 1] <= rest {
+                position += step;
+                rest -= self.tree[position - 1];
+            }
+            step >>= 1;
+        }
+        self.elements.get(position)
+    }
+
+    /// Chooses an element with probability proportional to its weight,
+    /// `None` if all weights are zero
+    pub fn sample(&self, rand: &mut impl Rng) -> Option<&T> {
+        match self.total() {
+            0 => None,
+            total => self.choose(rand.gen_range(0..total)),
+        }
+    }
+
+    /// Returns weight of `index`-th element
+    pub fn weight(&self, index: usize) -> usize {
+        assert!(index < self.tree.len(), "index {index} is out of bounds");
+        self.prefix(index + 1) - self.prefix(index)
+    }
+
+    /// Returns weights of all elements in O(n) time
+    pub fn weights(&self) -> Vec<usize> {
+        let mut weights = self.tree.clone();
+        for i in (1..=weights.len()).rev() {
+            let parent = i + lowbit(i);
+            if parent <= weights.len() {
+                weights[parent - 1] -= weights[i - 1];
+            }
+        }
+        weights
+    }
+
+    /// Returns elements with their weights
+    pub fn iter(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.elements.iter().zip(self.weights())
+    }
+
+    /// Adds `delta` to weight of `index`-th element
+    pub fn increment(&mut self, index: usize, delta: usize) {
+        assert!(index < self.tree.len(), "index {index} is out of bounds");
+        let mut i = index + 1;
+        while i <= self.tree.len() {
+            self.tree[i - 1] += delta;
+            i += lowbit(i);
+        }
+    }
+
+    /// Subtracts `delta` from weight of `index`-th element,
+    /// panics if the weight is less than `delta`
+    pub fn decrement(&mut self, index: usize, delta: usize) {
+        assert!(self.weight(index) >= delta, "weight of element {index} is less than {delta}");
+        let mut i = index + 1;
+        while i <= self.tree.len() {
+            self.tree[i - 1] -= delta;
+            i += lowbit(i);
+        }
+    }
+
+    /// Replaces weight of `index`-th element
+    pub fn set_weight(&mut self, index: usize, weight: usize) {
+        let old = self.weight(index);
+        if weight > old {
+            self.increment(index, weight - old);
+        } else {
+            self.decrement(index, old - weight);
+        }
+    }
+
+    /// Removes `index`-th element returning it and its weight,
+    /// the last element takes its place like in `Vec::swap_remove`
+    pub fn swap_remove(&mut self, index: usize) -> (T, usize) {
+        let weight = self.weight(index);
+        let last = self.tree.len() - 1;
+        let last_weight = self.weight(last);
+        // Nodes before the last one don't cover it, so it's dropped once its weight is zero
+        self.decrement(last, last_weight);
+        self.tree.pop();
+        if index != last {
+            self.set_weight(index, last_weight);
+        }
+        (self.elements.swap_remove(index), weight)
+    }
+
+    /// Keeps only elements for which `f(element, weight)` returns `true`,
+    /// order of kept elements is preserved, takes O(n) time
+    pub fn retain(&mut self, mut f: impl FnMut(&T, usize) -> bool) {
+        let weights = self.weights();
+        let mut kept_weights = Vec::new();
+        let mut i = 0;
+        self.elements.retain(|x| {
+            let keep = f(x, weights[i]);
+            if keep {
+                kept_weights.push(weights[i]);
+            }
+            i += 1;
+            keep
+        });
+        *self = Self::from_weights(std::mem::take(&mut self.elements), kept_weights);
+    }
+
+    /// Returns sum of weights of all elements
+    pub fn total(&self) -> usize {
+        self.prefix(self.tree.len())
+    }
+
+    /// Returns count of collection's elements
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Returns `true` if collection has no elements
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Returns sum of weights of the first `count` elements
+    fn prefix(&self, count: usize) -> usize {
+        let mut sum = 0;
+        let mut i = count;
+        while i > 0 {
+            sum += self.tree[i - 1];
+            i -= lowbit(i);
+        }
+        sum
+    }
+}
+
+impl<T> Default for WeightedTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<WeightedVec<T>> for WeightedTree<T> {
+    fn from(value: WeightedVec<T>) -> Self {
+        let weights = (0..value.len()).map(|i| value.weight(i)).collect();
+        Self::from_weights(value.elements, weights)
+    }
+}
+
+/// Returns the lowest set bit of the number
+fn lowbit(i: usize) -> usize {
+    i & i.wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choice() {
+        let mut tree: WeightedTree<usize> = WeightedTree::new();
+        tree.push(10, 0);
+        tree.push(10, 1);
+        tree.push(10, 2);
+
+        assert_eq!(tree.choose(0), Some(&0));
+        assert_eq!(tree.choose(9), Some(&0));
+        assert_eq!(tree.choose(10), Some(&1));
+        assert_eq!(tree.choose(29), Some(&2));
+        assert_eq!(tree.choose(30), None);
+    }
+
+    #[test]
+    fn updates() {
+        let weights = vec![3, 0, 7, 1, 1, 12, 5];
+        let mut tree = WeightedTree::from_weights((0..weights.len()).collect(), weights.clone());
+        let mut pushed = WeightedTree::new();
+        for (i, weight) in weights.iter().enumerate() {
+            pushed.push(*weight, i);
+        }
+        assert_eq!(pushed.weights(), weights);
+        assert_eq!(tree.weights(), weights);
+        assert_eq!(tree.total(), 29);
+
+        tree.increment(1, 4);
+        tree.decrement(5, 10);
+        assert_eq!(tree.weights(), vec![3, 4, 7, 1, 1, 2, 5]);
+        assert_eq!(tree.choose(3), Some(&1));
+        assert_eq!(tree.choose(22), Some(&6));
+
+        assert_eq!(tree.swap_remove(2), (2, 7));
+        assert_eq!(tree.elements, vec![0, 1, 6, 3, 4, 5]);
+        assert_eq!(tree.weights(), vec![3, 4, 5, 1, 1, 2]);
+        assert_eq!(tree.swap_remove(5), (5, 2));
+        assert_eq!(tree.total(), 14);
+
+        tree.set_weight(0, 0);
+        tree.retain(|_, weight| weight > 1);
+        assert_eq!(tree.elements, vec![1, 6]);
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![(&1, 4), (&6, 5)]);
+        assert_eq!(tree.choose(4), Some(&6));
+    }
+}
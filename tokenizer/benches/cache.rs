@@ -0,0 +1,41 @@
+//! Compares encoding of `content/corpus.txt` with and without encoding cache:
+//! `cargo bench -p tokenizer --bench cache`
+
+use std::time::{Duration, Instant};
+
+use tokenizer::{BpeTokenizer, PreTokenizer, Tokenizer, TokenizerPipeline, Vocabulary};
+
+const RUNS: u32 = 5;
+
+fn measure(pipeline: &TokenizerPipeline, corpus: &str) -> (Duration, Vec<u32>) {
+    let mut tokens = Vec::new();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        tokens = pipeline.tokenize(corpus);
+    }
+    (start.elapsed() / RUNS, tokens)
+}
+
+fn main() {
+    let corpus = include_str!("../../content/corpus.txt");
+
+    let tokenizer = BpeTokenizer::builder()
+        .max_size(4000)
+        .pre_tokenizer(PreTokenizer::Words)
+        .build();
+    let mut vocab = Vocabulary::new();
+    tokenizer.fill_vocab(corpus, &mut vocab);
+
+    let plain = TokenizerPipeline::new(Box::new(tokenizer.clone()), vocab.clone());
+    let cached = TokenizerPipeline::new(Box::new(tokenizer), vocab).with_cache(100_000);
+
+    let (plain_time, plain_tokens) = measure(&plain, corpus);
+    let (cached_time, cached_tokens) = measure(&cached, corpus);
+    assert_eq!(plain_tokens, cached_tokens);
+
+    let stats = cached.cache_stats().unwrap();
+    println!("corpus.txt: {} bytes, {} tokens, {RUNS} runs", corpus.len(), plain_tokens.len());
+    println!("without cache: {plain_time:?} per run");
+    println!("with cache:    {cached_time:?} per run, {:.2}x", plain_time.as_secs_f64() / cached_time.as_secs_f64());
+    println!("hit rate {:.1}%, {} pieces cached", stats.hit_rate() * 100.0, stats.len);
+}
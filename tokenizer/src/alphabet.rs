@@ -0,0 +1,231 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+
+/// Atomic units of text: base tokens created by training
+/// and steps of greedy token extension during tokenization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Alphabet {
+    /// Every `char` is a unit, combining marks become separate tokens
+    #[default]
+    Chars,
+
+    /// Every extended grapheme cluster is a unit, so a letter with its
+    /// combining marks or an emoji sequence is never split, see [`graphemes`]
+    Graphemes,
+}
+
+impl Alphabet {
+    /// Splits text into units, concatenation of units is equal to text
+    pub fn units<'a>(&self, text: &'a str) -> Units<'a> {
+        Units {
+            text,
+            alphabet: *self,
+        }
+    }
+
+    /// Returns byte length of the first unit of text, `0` for empty text
+    pub fn next_unit_len(&self, text: &str) -> usize {
+        match self {
+            Alphabet::Chars => text.chars().next().map_or(0, char::len_utf8),
+            Alphabet::Graphemes => next_grapheme_len(text),
+        }
+    }
+}
+
+impl fmt::Display for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alphabet::Chars => write!(f, "chars"),
+            Alphabet::Graphemes => write!(f, "graphemes"),
+        }
+    }
+}
+
+impl FromStr for Alphabet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chars" => Ok(Alphabet::Chars),
+            "graphemes" => Ok(Alphabet::Graphemes),
+            x => Err(format!("unknown alphabet {x}, expected chars or graphemes")),
+        }
+    }
+}
+
+/// Iterator over units of text, produced by [`Alphabet::units`]
+#[derive(Debug, Clone)]
+pub struct Units<'a> {
+    text: &'a str,
+    alphabet: Alphabet,
+}
+
+impl<'a> Iterator for Units<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let (unit, rest) = self.text.split_at(self.alphabet.next_unit_len(self.text));
+        self.text = rest;
+        Some(unit)
+    }
+}
+
+/// Splits text into extended grapheme clusters.
+///
+/// Boundary rules of [UAX #29](https://www.unicode.org/reports/tr29/) are
+/// implemented except Indic conjuncts (GB9c), break properties cover Latin,
+/// Cyrillic, Greek, Hebrew, Arabic, Devanagari, Bengali, Thai, Hangul and emoji.
+pub fn graphemes(text: &str) -> Units<'_> {
+    Alphabet::Graphemes.units(text)
+}
+
+/// Grapheme cluster break property of a character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Break {
+    Cr,
+    Lf,
+    Control,
+    Extend,
+    Zwj,
+    RegionalIndicator,
+    Prepend,
+    SpacingMark,
+    L,
+    V,
+    T,
+    Lv,
+    Lvt,
+    Pictographic,
+    Other,
+}
+
+impl Break {
+    fn of(ch: char) -> Self {
+        match ch {
+            '\r' => Break::Cr,
+            '\n' => Break::Lf,
+            '\u{200D}' => Break::Zwj,
+            '\u{0}'..='\u{1F}' | '\u{7F}'..='\u{9F}' | '\u{AD}' | '\u{200B}' | '\u{200E}' | '\u{200F}'
+            | '\u{2028}'..='\u{202E}' | '\u{2060}'..='\u{206F}' | '\u{FEFF}' | '\u{FFF0}'..='\u{FFFB}' => Break::Control,
+            '\u{300}'..='\u{36F}' | '\u{483}'..='\u{489}' | '\u{591}'..='\u{5BD}' | '\u{5BF}' | '\u{5C1}' | '\u{5C2}'
+            | '\u{5C4}' | '\u{5C5}' | '\u{5C7}' | '\u{610}'..='\u{61A}' | '\u{64B}'..='\u{65F}' | '\u{670}'
+            | '\u{6D6}'..='\u{6DC}' | '\u{6DF}'..='\u{6E4}' | '\u{6E7}' | '\u{6E8}' | '\u{6EA}'..='\u{6ED}'
+            | '\u{900}'..='\u{902}' | '\u{93A}' | '\u{93C}' | '\u{941}'..='\u{948}' | '\u{94D}' | '\u{951}'..='\u{957}'
+            | '\u{962}' | '\u{963}' | '\u{981}' | '\u{9BC}' | '\u{9BE}' | '\u{9C1}'..='\u{9C4}' | '\u{9CD}' | '\u{9D7}'
+            | '\u{9E2}' | '\u{9E3}' | '\u{E31}' | '\u{E34}'..='\u{E3A}' | '\u{E47}'..='\u{E4E}' | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}' | '\u{200C}' | '\u{20D0}'..='\u{20FF}' | '\u{302A}'..='\u{302F}' | '\u{3099}'
+            | '\u{309A}' | '\u{FE00}'..='\u{FE0F}' | '\u{FE20}'..='\u{FE2F}' | '\u{FF9E}' | '\u{FF9F}'
+            | '\u{1F3FB}'..='\u{1F3FF}' | '\u{E0020}'..='\u{E007F}' | '\u{E0100}'..='\u{E01EF}' => Break::Extend,
+            '\u{1F1E6}'..='\u{1F1FF}' => Break::RegionalIndicator,
+            '\u{600}'..='\u{605}' | '\u{6DD}' | '\u{70F}' | '\u{890}' | '\u{891}' | '\u{8E2}' | '\u{D4E}' => Break::Prepend,
+            '\u{903}' | '\u{93B}' | '\u{93E}'..='\u{940}' | '\u{949}'..='\u{94C}' | '\u{94E}' | '\u{94F}' | '\u{982}'
+            | '\u{983}' | '\u{9BF}' | '\u{9C0}' | '\u{9C7}' | '\u{9C8}' | '\u{9CB}' | '\u{9CC}' | '\u{E33}' => Break::SpacingMark,
+            '\u{1100}'..='\u{115F}' | '\u{A960}'..='\u{A97C}' => Break::L,
+            '\u{1160}'..='\u{11A7}' | '\u{D7B0}'..='\u{D7C6}' => Break::V,
+            '\u{11A8}'..='\u{11FF}' | '\u{D7CB}'..='\u{D7FB}' => Break::T,
+            // Precomposed syllables without final consonant are every 28th
+            '\u{AC00}'..='\u{D7A3}' if (ch as u32 - 0xAC00).is_multiple_of(28) => Break::Lv,
+            '\u{AC00}'..='\u{D7A3}' => Break::Lvt,
+            '\u{A9}' | '\u{AE}' | '\u{203C}' | '\u{2049}' | '\u{2122}' | '\u{2139}' | '\u{2194}'..='\u{2199}'
+            | '\u{21A9}' | '\u{21AA}' | '\u{231A}' | '\u{231B}' | '\u{2328}' | '\u{23CF}' | '\u{23E9}'..='\u{23F3}'
+            | '\u{23F8}'..='\u{23FA}' | '\u{24C2}' | '\u{25AA}' | '\u{25AB}' | '\u{25B6}' | '\u{25C0}'
+            | '\u{25FB}'..='\u{25FE}' | '\u{2600}'..='\u{27BF}' | '\u{2934}' | '\u{2935}' | '\u{2B05}'..='\u{2B07}'
+            | '\u{2B1B}' | '\u{2B1C}' | '\u{2B50}' | '\u{2B55}' | '\u{3030}' | '\u{303D}' | '\u{3297}' | '\u{3299}'
+            | '\u{1F000}'..='\u{1F0FF}' | '\u{1F10D}'..='\u{1F10F}' | '\u{1F12F}' | '\u{1F16C}'..='\u{1F171}'
+            | '\u{1F17E}' | '\u{1F17F}' | '\u{1F18E}' | '\u{1F191}'..='\u{1F19A}' | '\u{1F1AD}'..='\u{1F1E5}'
+            | '\u{1F201}'..='\u{1F20F}' | '\u{1F21A}' | '\u{1F22F}' | '\u{1F232}'..='\u{1F23A}'
+            | '\u{1F23C}'..='\u{1F23F}' | '\u{1F249}'..='\u{1F3FA}' | '\u{1F400}'..='\u{1F53D}'
+            | '\u{1F546}'..='\u{1F64F}' | '\u{1F680}'..='\u{1F6FF}' | '\u{1F774}'..='\u{1F77F}'
+            | '\u{1F7D5}'..='\u{1F7FF}' | '\u{1F80C}'..='\u{1F80F}' | '\u{1F848}'..='\u{1F84F}'
+            | '\u{1F85A}'..='\u{1F85F}' | '\u{1F888}'..='\u{1F88F}' | '\u{1F8AE}'..='\u{1F8FF}'
+            | '\u{1F90C}'..='\u{1F93A}' | '\u{1F93C}'..='\u{1F945}' | '\u{1F947}'..='\u{1FAFF}'
+            | '\u{1FC00}'..='\u{1FFFD}' => Break::Pictographic,
+            _ => Break::Other,
+        }
+    }
+}
+
+/// Returns `true` for combining marks, which are parts of letters, e.g. stress marks in Russian text.
+/// These are Extend characters except joiners, variation selectors, emoji modifiers and tags
+pub(crate) fn is_combining_mark(ch: char) -> bool {
+    let not_mark = matches!(ch, '\u{200C}' | '\u{FE00}'..='\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}')
+        || matches!(ch, '\u{E0020}'..='\u{E007F}' | '\u{E0100}'..='\u{E01EF}');
+    Break::of(ch) == Break::Extend && !not_mark
+}
+
+/// Returns byte length of the first grapheme cluster of text
+fn next_grapheme_len(text: &str) -> usize {
+    let mut chars = text.char_indices();
+    let Some((_, first)) = chars.next() else {
+        return 0;
+    };
+    let mut prev = Break::of(first);
+    // Count of regional indicators in a row before current character
+    let mut regional = usize::from(prev == Break::RegionalIndicator);
+    // Pictographic character followed by Extend* was seen (GB11)
+    let mut pictographic = prev == Break::Pictographic;
+
+    for (i, ch) in chars {
+        let next = Break::of(ch);
+        let joined = match (prev, next) {
+            (Break::Cr, Break::Lf) => true,
+            (Break::Cr | Break::Lf | Break::Control, _) | (_, Break::Cr | Break::Lf | Break::Control) => false,
+            (Break::L, Break::L | Break::V | Break::Lv | Break::Lvt) => true,
+            (Break::Lv | Break::V, Break::V | Break::T) => true,
+            (Break::Lvt | Break::T, Break::T) => true,
+            (_, Break::Extend | Break::Zwj | Break::SpacingMark) => true,
+            (Break::Prepend, _) => true,
+            (Break::Zwj, Break::Pictographic) => pictographic,
+            (Break::RegionalIndicator, Break::RegionalIndicator) => regional % 2 == 1,
+            _ => false,
+        };
+        if !joined {
+            return i;
+        }
+
+        pictographic = match next {
+            Break::Pictographic => true,
+            Break::Extend => pictographic && prev != Break::Zwj,
+            // ZWJ keeps the state only directly after Pictographic Extend*
+            Break::Zwj => pictographic && prev != Break::Zwj,
+            _ => false,
+        };
+        regional = if next == Break::RegionalIndicator { regional + 1 } else { 0 };
+        prev = next;
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn graphemes() {
+        let split = |text| super::graphemes(text).collect::<Vec<_>>();
+        assert_eq!(split("ла\u{301}д"), vec!["л", "а\u{301}", "д"]);
+        assert_eq!(split("a\r\nb\n\r"), vec!["a", "\r\n", "b", "\n", "\r"]);
+        // Family emoji joined by ZWJ and a skin tone modifier
+        assert_eq!(split("👨\u{200D}👩\u{200D}👧!👍🏽"), vec!["👨\u{200D}👩\u{200D}👧", "!", "👍🏽"]);
+        // Flags are pairs of regional indicators
+        assert_eq!(split("🇫🇷🇩🇪🇮"), vec!["🇫🇷", "🇩🇪", "🇮"]);
+        assert_eq!(split("\u{1100}\u{1161}\u{11A8}한"), vec!["\u{1100}\u{1161}\u{11A8}", "한"]);
+        assert_eq!(split("नमस्ते"), vec!["न", "म", "स्", "ते"]);
+        assert_eq!(split("\u{301}a"), vec!["\u{301}", "a"]);
+        assert_eq!(split("a\u{200D}b"), vec!["a\u{200D}", "b"]);
+        assert_eq!(split(""), Vec::<&str>::new());
+
+        assert_eq!(Alphabet::Chars.units("а\u{301}").count(), 2);
+        assert_eq!("graphemes".parse(), Ok(Alphabet::Graphemes));
+        assert_eq!(Alphabet::default().to_string(), "chars");
+        assert!("bytes".parse::<Alphabet>().is_err());
+    }
+}
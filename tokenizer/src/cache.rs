@@ -0,0 +1,165 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, RandomState};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::tokenizers::TokenId;
+
+/// Count of independently locked parts of the cache,
+/// threads encoding different pieces rarely wait for each other
+const SHARDS: usize = 16;
+
+/// Longer pieces aren't cached, e.g. whole documents when
+/// vocabulary has no pre-tokenizer
+pub const MAX_CACHED_PIECE_LEN: usize = 256;
+
+/// Bounded thread-safe cache of piece tokens, see [`crate::TokenizerPipeline::with_cache`].
+///
+/// When a part of the cache is full, its oldest piece is evicted.
+pub struct EncodingCache {
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Shard {
+    /// Piece -> tokens with ranges relative to the piece
+    tokens: HashMap<String, Vec<(TokenId, Range<usize>)>>,
+
+    /// Pieces in insertion order
+    order: VecDeque<String>,
+}
+
+impl EncodingCache {
+    /// Creates cache holding at most `capacity` pieces
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+            hasher: RandomState::new(),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Appends cached tokens of the piece, on miss `tokenize` appends them
+    /// and the result is remembered
+    pub fn tokenize(
+        &self,
+        piece: &str,
+        tokens: &mut Vec<(TokenId, Range<usize>)>,
+        tokenize: impl FnOnce(&mut Vec<(TokenId, Range<usize>)>),
+    ) {
+        let index = self.hasher.hash_one(piece) as usize % SHARDS;
+        let capacity = self.shard_capacity(index);
+        if capacity == 0 || piece.len() > MAX_CACHED_PIECE_LEN {
+            tokenize(tokens);
+            return;
+        }
+
+        let shard = &self.shards[index];
+        if let Some(cached) = shard.lock().unwrap().tokens.get(piece) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            tokens.extend_from_slice(cached);
+            return;
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let start = tokens.len();
+        tokenize(tokens);
+
+        let mut shard = shard.lock().unwrap();
+        if shard.tokens.contains_key(piece) {
+            return;
+        }
+        if shard.order.len() >= capacity {
+            if let Some(oldest) = shard.order.pop_front() {
+                shard.tokens.remove(&oldest);
+            }
+        }
+        shard.tokens.insert(piece.to_string(), tokens[start..].to_vec());
+        shard.order.push_back(piece.to_string());
+    }
+
+    /// Capacity is split between shards, so together they hold at most `capacity` pieces
+    fn shard_capacity(&self, index: usize) -> usize {
+        self.capacity / SHARDS + usize::from(index < self.capacity % SHARDS)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            len: self.shards.iter().map(|x| x.lock().unwrap().order.len()).sum(),
+            capacity: self.capacity,
+        }
+    }
+
+    /// Removes all pieces and resets statistics
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            *shard.lock().unwrap() = Shard::default();
+        }
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Usage statistics of [`EncodingCache`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+
+    /// Count of cached pieces
+    pub len: usize,
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    /// Returns share of lookups found in the cache
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache() {
+        let cache = EncodingCache::new(SHARDS);
+        let mut tokens = Vec::new();
+        cache.tokenize("ab", &mut tokens, |x| x.push((1, 0..2)));
+        cache.tokenize("ab", &mut tokens, |_| panic!("piece should be cached"));
+        assert_eq!(tokens, vec![(1, 0..2), (1, 0..2)]);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.len), (1, 1, 1));
+        assert_eq!(stats.hit_rate(), 0.5);
+
+        // Every shard holds one piece
+        for i in 0..100 {
+            cache.tokenize(&i.to_string(), &mut tokens, |x| x.push((i, 0..1)));
+        }
+        assert!(cache.stats().len <= SHARDS);
+
+        cache.clear();
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 0, len: 0, capacity: SHARDS });
+
+        let cache = EncodingCache::new(1);
+        for i in 0..100 {
+            cache.tokenize(&i.to_string(), &mut tokens, |x| x.push((i, 0..1)));
+        }
+        assert_eq!(cache.stats().len, 1);
+    }
+}
@@ -0,0 +1,115 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// Part of training corpus with its sampling weight
+#[derive(Debug, Clone)]
+pub struct CorpusSource {
+    /// Documents of the source, token pairs never cross document boundaries
+    pub documents: Vec<String>,
+
+    /// Multiplier applied to pair counts of the source, weight `2.0`
+    /// counts as if the source was repeated twice
+    pub weight: f64,
+}
+
+impl CorpusSource {
+    pub fn new(documents: Vec<String>, weight: f64) -> Self {
+        Self {
+            documents,
+            weight,
+        }
+    }
+
+    /// Reads source from a file or from every `.txt` file in a directory
+    /// (recursively, in path order). Each file becomes a separate document.
+    #[cfg(feature = "std")]
+    pub fn from_path(path: &Path, weight: f64) -> Result<Self, std::io::Error> {
+        let mut files = Vec::new();
+        collect_files(path, &mut files)?;
+
+        let mut documents = Vec::new();
+        for file in files {
+            documents.push(std::fs::read_to_string(file)?);
+        }
+        Ok(Self::new(documents, weight))
+    }
+
+    /// Returns count of characters in all documents
+    pub fn len(&self) -> usize {
+        self.documents.iter().map(|x| x.chars().count()).sum()
+    }
+
+    /// Returns `true` if source has no characters
+    pub fn is_empty(&self) -> bool {
+        self.documents.iter().all(|x| x.is_empty())
+    }
+}
+
+#[cfg(feature = "std")]
+fn collect_files(path: &Path, files: &mut Vec<std::path::PathBuf>) -> Result<(), std::io::Error> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries: Vec<std::path::PathBuf> = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|x| x.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, files)?;
+        } else if entry.extension().is_some_and(|x| x == "txt") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Rescales weights of sources so each source gets a share of training
+/// data proportional to `len^(1 / temperature)`.
+///
+/// Temperature `1.0` keeps natural proportions, bigger temperatures move
+/// shares towards uniform, so small sources are not dominated by big ones.
+/// Total amount of training data stays the same.
+#[cfg(feature = "std")]
+pub fn apply_temperature(sources: &mut [CorpusSource], temperature: f64) {
+    let lens: Vec<f64> = sources.iter().map(|x| x.len() as f64).collect();
+    let total: f64 = lens.iter().sum();
+    let shares: Vec<f64> = lens.iter().map(|x| x.powf(1.0 / temperature)).collect();
+    let shares_total: f64 = shares.iter().sum();
+
+    for ((source, len), share) in sources.iter_mut().zip(lens).zip(shares) {
+        if len > 0.0 {
+            source.weight *= share / shares_total * total / len;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn source(len: usize) -> CorpusSource {
+        CorpusSource::new(vec!["a".repeat(len)], 1.0)
+    }
+
+    #[test]
+    fn temperature_one() {
+        let mut sources = vec![source(100), source(400)];
+        apply_temperature(&mut sources, 1.0);
+        assert!((sources[0].weight - 1.0).abs() < 1e-9);
+        assert!((sources[1].weight - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn temperature_uniform() {
+        let mut sources = vec![source(100), source(400)];
+        apply_temperature(&mut sources, 1e9);
+        assert!((sources[0].weight - 2.5).abs() < 1e-6);
+        assert!((sources[1].weight - 0.625).abs() < 1e-6);
+    }
+}
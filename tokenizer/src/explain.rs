@@ -0,0 +1,250 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::tokenizers::{TokenId, Tokenizer};
+use crate::vocab::Vocabulary;
+
+/// How token appeared in vocabulary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenOrigin {
+    /// Token isn't a merge of other tokens, e.g. a single character
+    Base,
+
+    /// Token is produced by recorded merge
+    Merge {
+        rank: usize,
+        count: u32,
+    },
+
+    /// Vocabulary has no recorded merge for the token,
+    /// parts are guessed from tokens added before it
+    Inferred,
+}
+
+/// Tree of merges which produced a token
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeTree {
+    pub token: TokenId,
+    pub value: String,
+    pub origin: TokenOrigin,
+    /// Left and right merged tokens, `None` for base tokens
+    pub parts: Option<Box<(MergeTree, MergeTree)>>,
+}
+
+impl MergeTree {
+    /// Builds tree of merges for token, if token is unknown returns `None`.
+    ///
+    /// Recorded merge is used only if its parts precede the token: have smaller id
+    /// or were produced by merges of lower rank, and aren't being expanded already.
+    /// Otherwise parts are inferred like for tokens without recorded merge,
+    /// so inconsistent merges of a hand-edited vocabulary can't loop.
+    pub fn new(token: TokenId, vocab: &Vocabulary) -> Option<Self> {
+        Self::build(token, vocab, &mut Vec::new())
+    }
+
+    fn build(token: TokenId, vocab: &Vocabulary, ancestors: &mut Vec<TokenId>) -> Option<Self> {
+        let value = vocab.get_token(token)?.to_string();
+        ancestors.push(token);
+
+        let precedes = |part: TokenId, rank: usize| {
+            let earlier = part < token || vocab.get_merge(part).is_some_and(|(x, _)| x < rank);
+            earlier && !ancestors.contains(&part)
+        };
+        let merge = vocab.get_merge(token)
+            .filter(|(rank, merge)| precedes(merge.left, *rank) && precedes(merge.right, *rank))
+            .map(|(rank, merge)| ((merge.left, merge.right), TokenOrigin::Merge { rank, count: merge.count }));
+        let parts = merge.or_else(|| {
+            infer_parts(token, &value, vocab)
+                .filter(|(left, right)| !ancestors.contains(left) && !ancestors.contains(right))
+                .map(|parts| (parts, TokenOrigin::Inferred))
+        });
+
+        let result = match parts {
+            Some(((left, right), origin)) => {
+                let left = Self::build(left, vocab, ancestors)?;
+                let right = Self::build(right, vocab, ancestors)?;
+                Self { token, value, origin, parts: Some(Box::new((left, right))) }
+            },
+            None => Self { token, value, origin: TokenOrigin::Base, parts: None },
+        };
+        ancestors.pop();
+        Some(result)
+    }
+
+    /// Returns merged tokens of the tree in order they were merged
+    pub fn merges(&self) -> Vec<&MergeTree> {
+        let mut result = Vec::new();
+        if let Some(parts) = &self.parts {
+            result.extend(parts.0.merges());
+            result.extend(parts.1.merges());
+            result.push(self);
+        }
+        result
+    }
+
+    fn fmt_node(&self, f: &mut fmt::Formatter<'_>, prefix: &str, child_prefix: &str) -> fmt::Result {
+        write!(f, "{prefix}{:?} ({})", self.value, self.token)?;
+        match self.origin {
+            TokenOrigin::Base => writeln!(f)?,
+            TokenOrigin::Merge { rank, count } => writeln!(f, " merge #{rank}, count {count}")?,
+            TokenOrigin::Inferred => writeln!(f, " inferred")?,
+        }
+
+        if let Some(parts) = &self.parts {
+            parts.0.fmt_node(f, &format!("{child_prefix}├─ "), &format!("{child_prefix}│  "))?;
+            parts.1.fmt_node(f, &format!("{child_prefix}└─ "), &format!("{child_prefix}   "))?;
+        }
+        Ok(())
+    }
+
+    fn write_dot(&self, result: &mut String, next_node: &mut usize) -> usize {
+        let node = *next_node;
+        *next_node += 1;
+
+        let origin = match self.origin {
+            TokenOrigin::Base => String::new(),
+            TokenOrigin::Merge { rank, count } => format!("\\nmerge #{rank}, count {count}"),
+            TokenOrigin::Inferred => "\\ninferred".to_string(),
+        };
+        let value = format!("{:?}", self.value).replace('\\', "\\\\").replace('"', "\\\"");
+        result.push_str(&format!("    n{node} [label=\"{value}\\n{}{origin}\"];\n", self.token));
+
+        if let Some(parts) = &self.parts {
+            let left = parts.0.write_dot(result, next_node);
+            let right = parts.1.write_dot(result, next_node);
+            result.push_str(&format!("    n{node} -> n{left};\n    n{node} -> n{right};\n"));
+        }
+        node
+    }
+}
+
+impl fmt::Display for MergeTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_node(f, "", "")
+    }
+}
+
+// Finds the first split of token between units of the alphabet into two tokens added before it
+fn infer_parts(token: TokenId, value: &str, vocab: &Vocabulary) -> Option<(TokenId, TokenId)> {
+    let splits = vocab.alphabet().units(value).scan(0, |i, unit| {
+        *i += unit.len();
+        Some(*i)
+    });
+    splits.take_while(|i| *i < value.len()).find_map(|i| {
+        let left = vocab.get_token_id(&value[..i])?;
+        let right = vocab.get_token_id(&value[i..])?;
+        (left < token && right < token).then_some((left, right))
+    })
+}
+
+/// Returns two tokens merged into given token, either by recorded merge
+/// or guessed from tokens added before it. `None` for base and unknown tokens.
+pub fn parents(token: TokenId, vocab: &Vocabulary) -> Option<(TokenId, TokenId)> {
+    match vocab.get_merge(token) {
+        Some((_, merge)) => Some((merge.left, merge.right)),
+        None => infer_parts(token, vocab.get_token(token)?, vocab),
+    }
+}
+
+/// Tokenizes text and returns tree of merges for every token
+pub fn explain(tokenizer: &dyn Tokenizer, text: &str, vocab: &Vocabulary) -> Vec<MergeTree> {
+    tokenizer.tokenize(text, vocab)
+        .into_iter()
+        .filter_map(|token| MergeTree::new(token, vocab))
+        .collect()
+}
+
+/// Exports merge trees as Graphviz DOT graph
+pub fn to_dot(trees: &[MergeTree]) -> String {
+    let mut result = String::from("digraph merges {\n    node [shape=box];\n");
+    let mut next_node = 0;
+    for tree in trees {
+        tree.write_dot(&mut result, &mut next_node);
+    }
+    result.push_str("}\n");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tokenizers::BpeTokenizer;
+    use crate::vocab::Merge;
+
+    use super::*;
+
+    #[test]
+    fn merge_tree() {
+        let tokenizer = BpeTokenizer::new();
+        let mut vocab = Vocabulary::new();
+        tokenizer.fill_vocab("abcdabcd", &mut vocab);
+
+        let trees = explain(&tokenizer, "abcdab", &vocab);
+        assert_eq!(trees.iter().map(|x| x.value.as_str()).collect::<Vec<_>>(), vec!["ab", "cd", "ab"]);
+        assert_eq!(trees[0].merges().len(), 1);
+
+        let tree = MergeTree::new(6, &vocab).unwrap();
+        assert_eq!(tree.value, "abcd");
+        assert_eq!(tree.origin, TokenOrigin::Merge { rank: 2, count: 2 });
+        assert_eq!(tree.merges().iter().map(|x| x.value.as_str()).collect::<Vec<_>>(), vec!["ab", "cd", "abcd"]);
+        let expected = [
+            "\"abcd\" (6) merge #2, count 2",
+            "├─ \"ab\" (4) merge #0, count 2",
+            "│  ├─ \"a\" (0)",
+            "│  └─ \"b\" (1)",
+            "└─ \"cd\" (5) merge #1, count 2",
+            "   ├─ \"c\" (2)",
+            "   └─ \"d\" (3)",
+        ];
+        assert_eq!(tree.to_string(), expected.map(|x| x.to_string() + "\n").concat());
+
+        let dot = to_dot(&[tree]);
+        assert!(dot.starts_with("digraph merges {"));
+        assert!(dot.contains("n0 [label=\"\\\"abcd\\\"\\n6\\nmerge #2, count 2\"];"));
+        assert!(dot.contains("n0 -> n1;"));
+    }
+
+    #[test]
+    fn inferred() {
+        let vocab = crate::VocabularyBuilder::new()
+            .try_add_token("a".to_string())
+            .try_add_token("b".to_string())
+            .try_add_token("ab".to_string())
+            .build();
+
+        let tree = MergeTree::new(2, &vocab).unwrap();
+        assert_eq!(tree.origin, TokenOrigin::Inferred);
+        assert_eq!(tree.merges().len(), 1);
+        assert_eq!(MergeTree::new(0, &vocab).unwrap().origin, TokenOrigin::Base);
+        assert_eq!(MergeTree::new(5, &vocab), None);
+        assert_eq!(parents(2, &vocab), Some((0, 1)));
+        assert_eq!(parents(0, &vocab), None);
+    }
+
+    #[test]
+    fn inconsistent_merges() {
+        let mut vocab = crate::VocabularyBuilder::new()
+            .try_add_token("a".to_string())
+            .try_add_token("b".to_string())
+            .try_add_token("ab".to_string())
+            .try_add_token("c".to_string())
+            .build();
+        // Merge of a token with itself, and merges producing each other
+        vocab.add_merge(Merge { left: 3, right: 3, token: 3, count: 1 });
+        vocab.add_merge(Merge { left: 0, right: 0, token: 1, count: 1 });
+        vocab.add_merge(Merge { left: 1, right: 1, token: 0, count: 1 });
+        vocab.add_merge(Merge { left: 2, right: 2, token: 2, count: 1 });
+
+        assert_eq!(MergeTree::new(3, &vocab).unwrap().origin, TokenOrigin::Base);
+        let tree = MergeTree::new(2, &vocab).unwrap();
+        assert_eq!(tree.origin, TokenOrigin::Inferred);
+        assert_eq!(tree.merges().len(), 3);
+        // "b" precedes "a" by rank, but "a" is being expanded, so "b" is a base token
+        let tree = MergeTree::new(0, &vocab).unwrap();
+        assert_eq!(tree.origin, TokenOrigin::Merge { rank: 2, count: 1 });
+        assert_eq!(tree.merges().len(), 1);
+        assert_eq!(tree.parts.unwrap().0.origin, TokenOrigin::Base);
+    }
+}
@@ -0,0 +1,97 @@
+use core::fmt;
+use core::str::FromStr;
+
+/// Stable hash of [`crate::Vocabulary`] content.
+///
+/// Models record fingerprint of vocabulary they were trained with,
+/// so using them with another vocabulary can be detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    /// Returns error if fingerprints are different,
+    /// `self` is the expected fingerprint
+    pub fn verify(self, found: Fingerprint) -> Result<(), FingerprintMismatch> {
+        if self == found {
+            Ok(())
+        } else {
+            Err(FingerprintMismatch {
+                expected: self,
+                found,
+            })
+        }
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = core::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Fingerprint)
+    }
+}
+
+/// Error returned when model is used with a vocabulary it wasn't trained with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerprintMismatch {
+    /// Fingerprint of vocabulary used for training
+    pub expected: Fingerprint,
+
+    /// Fingerprint of given vocabulary
+    pub found: Fingerprint,
+}
+
+impl fmt::Display for FingerprintMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "vocabulary mismatch: model was trained with vocabulary {}, but vocabulary {} was given",
+            self.expected, self.found,
+        )
+    }
+}
+
+impl core::error::Error for FingerprintMismatch {}
+
+/// 64-bit FNV-1a hasher.
+///
+/// Unlike `std::hash::DefaultHasher` its output never changes
+/// between Rust versions and platforms.
+pub(crate) struct Fingerprinter {
+    state: u64,
+}
+
+impl Fingerprinter {
+    pub fn new() -> Self {
+        Self {
+            state: 0xcbf29ce484222325,
+        }
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Writes length-prefixed string, so `"ab" + "c"` differs from `"a" + "bc"`
+    pub fn write_str(&mut self, value: &str) {
+        self.write(&(value.len() as u64).to_le_bytes());
+        self.write(value.as_bytes());
+    }
+
+    pub fn finish(&self) -> Fingerprint {
+        Fingerprint(self.state)
+    }
+}
@@ -1,5 +1,46 @@
+//! BPE tokenizer.
+//!
+//! Builds without `std` (only `alloc` is needed) when default `std` feature
+//! is disabled, file reading is unavailable then.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+// Tests run on `std` even when the crate itself is built without it
+#[cfg(test)]
+#[macro_use]
+extern crate std;
+
+mod collections {
+    #[cfg(feature = "std")]
+    pub use std::collections::HashMap;
+
+    /// Hash maps need randomness from `std`, ordered map is used instead
+    #[cfg(not(feature = "std"))]
+    pub type HashMap<K, V> = alloc::collections::BTreeMap<K, V>;
+}
+
 mod vocab;
+mod corpus;
+mod fingerprint;
+mod stats;
+mod explain;
+mod normalizer;
+mod alphabet;
+mod pipeline;
+#[cfg(feature = "std")]
+mod cache;
 pub mod tokenizers;
 
 pub use vocab::*;
-pub use tokenizers::Tokenizer;
+pub use corpus::*;
+pub use fingerprint::*;
+pub use stats::*;
+pub use explain::*;
+pub use normalizer::*;
+pub use alphabet::*;
+pub use pipeline::*;
+#[cfg(feature = "std")]
+pub use cache::*;
+pub use tokenizers::{BpeTokenizer, BpeTokenizerBuilder, Tokenizer};
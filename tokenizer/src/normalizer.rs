@@ -0,0 +1,275 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::alphabet::is_combining_mark;
+
+/// Text transformations applied before tokenization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Normalizer {
+    /// Converts text to lowercase
+    pub lowercase: bool,
+
+    /// Replaces `\r\n` and `\r` with `\n`
+    pub line_endings: bool,
+}
+
+impl Normalizer {
+    /// Returns `true` if normalizer doesn't change text
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Applies enabled transformations to text
+    pub fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !self.changes(text) {
+            return Cow::Borrowed(text);
+        }
+        let mut result = String::with_capacity(text.len());
+        self.normalize_chars(text, |_, x| result.push(x));
+        Cow::Owned(result)
+    }
+
+    /// Applies enabled transformations to text and returns offset in original
+    /// text for every byte offset of normalized text, including its end
+    pub fn normalize_aligned<'a>(&self, text: &'a str) -> (Cow<'a, str>, Vec<usize>) {
+        if !self.changes(text) {
+            return (Cow::Borrowed(text), (0..=text.len()).collect());
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut offsets = Vec::with_capacity(text.len() + 1);
+        self.normalize_chars(text, |i, x| {
+            result.push(x);
+            offsets.extend(core::iter::repeat_n(i, x.len_utf8()));
+        });
+        offsets.push(text.len());
+        (Cow::Owned(result), offsets)
+    }
+
+    fn changes(&self, text: &str) -> bool {
+        (self.line_endings && text.contains('\r')) || (self.lowercase && text.chars().any(|x| x.is_uppercase()))
+    }
+
+    // Calls `push` with offset of original character for every normalized character
+    fn normalize_chars(&self, text: &str, mut push: impl FnMut(usize, char)) {
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, ch)) = chars.next() {
+            if self.line_endings && ch == '\r' {
+                chars.next_if(|x| x.1 == '\n');
+                push(i, '\n');
+            } else if self.lowercase && ch.is_uppercase() {
+                ch.to_lowercase().for_each(|x| push(i, x));
+            } else {
+                push(i, ch);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Normalizer {
+    /// Writes comma separated names of transformations or `none`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Vec::new();
+        if self.lowercase {
+            names.push("lowercase");
+        }
+        if self.line_endings {
+            names.push("line-endings");
+        }
+        if names.is_empty() {
+            names.push("none");
+        }
+        write!(f, "{}", names.join(","))
+    }
+}
+
+impl FromStr for Normalizer {
+    type Err = String;
+
+    /// Parses comma separated names of transformations or `none`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Self::default();
+        for name in s.split(',') {
+            match name.trim() {
+                "none" => {},
+                "lowercase" => result.lowercase = true,
+                "line-endings" => result.line_endings = true,
+                x => return Err(format!("unknown normalizer {x}, expected none, lowercase or line-endings")),
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Splits text into pieces which are tokenized separately,
+/// so tokens never cross piece boundaries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PreTokenizer {
+    /// Whole text is a single piece, tokens can span several words
+    #[default]
+    None,
+
+    /// Splits text into runs of whitespace and runs of other characters
+    Whitespace,
+
+    /// Splits text into runs of letters, digits, punctuation and whitespace,
+    /// single space before a run is kept with it: `"a  b."` -> `["a", " ", " b", "."]`
+    Words,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Letter,
+    Digit,
+    Space,
+    Other,
+}
+
+impl CharClass {
+    fn of(ch: char) -> Self {
+        if ch.is_whitespace() {
+            CharClass::Space
+        } else if ch.is_alphabetic() || is_combining_mark(ch) {
+            CharClass::Letter
+        } else if ch.is_numeric() {
+            CharClass::Digit
+        } else {
+            CharClass::Other
+        }
+    }
+}
+
+impl PreTokenizer {
+    /// Splits text into pieces, concatenation of pieces is equal to text
+    pub fn split<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        match self {
+            PreTokenizer::None => {
+                if text.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![text]
+                }
+            },
+            PreTokenizer::Whitespace => split_runs(text, |prev, ch| prev.is_whitespace() == ch.is_whitespace()),
+            PreTokenizer::Words => split_words(text),
+        }
+    }
+}
+
+fn split_runs(text: &str, same_run: impl Fn(char, char) -> bool) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    for (i, ch) in text.char_indices() {
+        if prev.is_some_and(|prev| !same_run(prev, ch)) {
+            pieces.push(&text[start..i]);
+            start = i;
+        }
+        prev = Some(ch);
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
+
+fn split_words(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut class: Option<CharClass> = None;
+    let mut prev: Option<(usize, char)> = None;
+    for (i, ch) in text.char_indices() {
+        let ch_class = CharClass::of(ch);
+        match class {
+            Some(current) if current == ch_class => {},
+            Some(CharClass::Space) => {
+                // Leaves the last space of whitespace run to the next piece
+                let (prev_i, prev_ch) = prev.unwrap();
+                if prev_ch != ' ' {
+                    pieces.push(&text[start..i]);
+                    start = i;
+                } else if prev_i > start {
+                    pieces.push(&text[start..prev_i]);
+                    start = prev_i;
+                }
+            },
+            Some(_) => {
+                pieces.push(&text[start..i]);
+                start = i;
+            },
+            None => {},
+        }
+        class = Some(ch_class);
+        prev = Some((i, ch));
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
+
+impl fmt::Display for PreTokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreTokenizer::None => write!(f, "none"),
+            PreTokenizer::Whitespace => write!(f, "whitespace"),
+            PreTokenizer::Words => write!(f, "words"),
+        }
+    }
+}
+
+impl FromStr for PreTokenizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(PreTokenizer::None),
+            "whitespace" => Ok(PreTokenizer::Whitespace),
+            "words" => Ok(PreTokenizer::Words),
+            x => Err(format!("unknown pre-tokenizer {x}, expected none, whitespace or words")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        let normalizer: Normalizer = "lowercase,line-endings".parse().unwrap();
+        assert_eq!(normalizer.normalize("Hello\r\nМир\r"), "hello\nмир\n");
+        let (text, offsets) = normalizer.normalize_aligned("A\r\nЖ");
+        assert_eq!(text, "a\nж");
+        assert_eq!(offsets, vec![0, 1, 3, 3, 5]);
+        assert_eq!(normalizer.to_string(), "lowercase,line-endings");
+        assert_eq!(Normalizer::default().to_string(), "none");
+        assert_eq!("none".parse(), Ok(Normalizer::default()));
+        assert!("upper".parse::<Normalizer>().is_err());
+    }
+
+    #[test]
+    fn split_whitespace() {
+        let pieces = PreTokenizer::Whitespace.split("hello  world!\n");
+        assert_eq!(pieces, vec!["hello", "  ", "world!", "\n"]);
+    }
+
+    #[test]
+    fn split_words() {
+        let pieces = PreTokenizer::Words.split("Hello,  world 42 times.\nМир");
+        assert_eq!(pieces, vec!["Hello", ",", " ", " world", " 42", " times", ".", "\n", "Мир"]);
+        assert_eq!(PreTokenizer::Words.split(" a"), vec![" a"]);
+        // Combining marks stay in words, emoji modifiers don't
+        assert_eq!(PreTokenizer::Words.split("мо\u{301}ре"), vec!["мо\u{301}ре"]);
+        assert_eq!(PreTokenizer::Words.split("\u{5D0}\u{591}\u{5D1}"), vec!["\u{5D0}\u{591}\u{5D1}"]);
+        assert_eq!(PreTokenizer::Words.split("a\u{1F44D}\u{1F3FB}"), vec!["a", "\u{1F44D}\u{1F3FB}"]);
+        assert_eq!(PreTokenizer::Words.split(""), Vec::<&str>::new());
+        assert_eq!(PreTokenizer::None.split("a b"), vec!["a b"]);
+    }
+}
@@ -0,0 +1,375 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+
+#[cfg(feature = "std")]
+use crate::cache::{CacheStats, EncodingCache};
+use crate::normalizer::{Normalizer, PreTokenizer};
+use crate::tokenizers::{BpeTokenizer, TokenId, Tokenizer};
+use crate::vocab::Vocabulary;
+
+/// Returns `true` for special tokens like `[UNK]`
+pub fn is_special_token(token: &str) -> bool {
+    token.len() > 2 && token.starts_with('[') && token.ends_with(']')
+}
+
+/// Returns model with given name, see [`Tokenizer::name`]
+pub fn model_by_name(name: &str) -> Option<Box<dyn Tokenizer>> {
+    match name {
+        "bpe" => Some(Box::new(BpeTokenizer::new())),
+        _ => None,
+    }
+}
+
+/// Transformation of tokens produced by the model
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PostProcessor {
+    #[default]
+    None,
+
+    /// Adds `start` token before and `end` token after tokens of the text
+    Wrap {
+        start: String,
+        end: String,
+    },
+}
+
+impl PostProcessor {
+    /// Applies post-processor to tokens of text with `len` bytes,
+    /// added tokens have empty ranges. Tokens missing in the vocabulary are not added.
+    pub fn process(&self, tokens: &mut Vec<(TokenId, Range<usize>)>, len: usize, vocab: &Vocabulary) {
+        match self {
+            PostProcessor::None => {},
+            PostProcessor::Wrap { start, end } => {
+                if let Some(start) = vocab.get_token_id(start) {
+                    tokens.insert(0, (start, 0..0));
+                }
+                if let Some(end) = vocab.get_token_id(end) {
+                    tokens.push((end, len..len));
+                }
+            },
+        }
+    }
+
+    /// Returns tokens added by post-processor, they should be in the vocabulary
+    pub fn special_tokens(&self) -> Vec<&str> {
+        match self {
+            PostProcessor::None => Vec::new(),
+            PostProcessor::Wrap { start, end } => Vec::from([start.as_str(), end.as_str()]),
+        }
+    }
+}
+
+impl fmt::Display for PostProcessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostProcessor::None => write!(f, "none"),
+            PostProcessor::Wrap { start, end } => write!(f, "wrap {start} {end}"),
+        }
+    }
+}
+
+impl FromStr for PostProcessor {
+    type Err = String;
+
+    /// Parses `none` or `wrap <start> <end>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(' ').collect();
+        match parts[..] {
+            ["none"] => Ok(PostProcessor::None),
+            ["wrap", start, end] if !start.is_empty() && !end.is_empty() => Ok(PostProcessor::Wrap {
+                start: start.to_string(),
+                end: end.to_string(),
+            }),
+            _ => Err(format!("unknown post-processor {s}, expected none or wrap <start> <end>")),
+        }
+    }
+}
+
+/// Conversion of tokens back to text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Decoder {
+    /// Concatenates values of tokens
+    #[default]
+    Concat,
+
+    /// Concatenates values of tokens skipping special tokens like `[UNK]`
+    SkipSpecial,
+}
+
+impl Decoder {
+    /// Converts tokens to text, unknown [`TokenId`]s are skipped
+    pub fn decode(self, tokens: &[TokenId], vocab: &Vocabulary) -> String {
+        let values = tokens.iter().filter_map(|x| vocab.get_token(*x));
+        match self {
+            Decoder::Concat => values.collect(),
+            Decoder::SkipSpecial => values.filter(|x| !is_special_token(x)).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decoder::Concat => write!(f, "concat"),
+            Decoder::SkipSpecial => write!(f, "skip-special"),
+        }
+    }
+}
+
+impl FromStr for Decoder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "concat" => Ok(Decoder::Concat),
+            "skip-special" => Ok(Decoder::SkipSpecial),
+            x => Err(format!("unknown decoder {x}, expected concat or skip-special")),
+        }
+    }
+}
+
+/// Complete tokenizer: vocabulary with its normalizer and pre-tokenizer,
+/// model, post-processor and decoder.
+///
+/// Pipeline is saved into a vocabulary file with extra `model`, `post_processor`
+/// and `decoder` lines, they are written only when differ from defaults,
+/// so a plain vocabulary file is a pipeline with BPE model.
+pub struct TokenizerPipeline {
+    vocab: Vocabulary,
+    model: Box<dyn Tokenizer>,
+    post_processor: PostProcessor,
+    decoder: Decoder,
+
+    #[cfg(feature = "std")]
+    cache: Option<EncodingCache>,
+}
+
+impl TokenizerPipeline {
+    pub fn new(model: Box<dyn Tokenizer>, vocab: Vocabulary) -> Self {
+        Self {
+            vocab,
+            model,
+            post_processor: PostProcessor::default(),
+            decoder: Decoder::default(),
+            #[cfg(feature = "std")]
+            cache: None,
+        }
+    }
+
+    pub fn with_post_processor(mut self, post_processor: PostProcessor) -> Self {
+        self.post_processor = post_processor;
+        self
+    }
+
+    pub fn with_decoder(mut self, decoder: Decoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Enables cache of tokens of pre-tokenized pieces holding at most `capacity` pieces.
+    ///
+    /// Natural text repeats the same words, so with a splitting pre-tokenizer
+    /// most words are tokenized once. Cached pieces are tokenized by
+    /// [`Tokenizer::tokenize_piece`] of the model.
+    #[cfg(feature = "std")]
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(EncodingCache::new(capacity));
+        self
+    }
+
+    /// Returns statistics of the cache, `None` if cache is disabled
+    #[cfg(feature = "std")]
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|x| x.stats())
+    }
+
+    pub fn vocab(&self) -> &Vocabulary {
+        &self.vocab
+    }
+
+    pub fn model(&self) -> &dyn Tokenizer {
+        self.model.as_ref()
+    }
+
+    /// Returns normalizer, it's a setting of the vocabulary
+    pub fn normalizer(&self) -> Normalizer {
+        self.vocab.normalizer()
+    }
+
+    /// Returns pre-tokenizer, it's a setting of the vocabulary
+    pub fn pre_tokenizer(&self) -> PreTokenizer {
+        self.vocab.pre_tokenizer()
+    }
+
+    pub fn post_processor(&self) -> &PostProcessor {
+        &self.post_processor
+    }
+
+    pub fn decoder(&self) -> Decoder {
+        self.decoder
+    }
+
+    /// Converts given text to sequence of [`TokenId`]
+    pub fn tokenize(&self, text: &str) -> Vec<TokenId> {
+        self.tokenize_with_offsets(text).into_iter().map(|x| x.0).collect()
+    }
+
+    /// Converts given text to sequence of [`TokenId`] with byte ranges
+    /// of tokens in the text
+    pub fn tokenize_with_offsets(&self, text: &str) -> Vec<(TokenId, Range<usize>)> {
+        let mut tokens = self.tokenize_model(text);
+        self.post_processor.process(&mut tokens, text.len(), &self.vocab);
+        tokens
+    }
+
+    #[cfg(feature = "std")]
+    fn tokenize_model(&self, text: &str) -> Vec<(TokenId, Range<usize>)> {
+        match &self.cache {
+            Some(cache) => crate::tokenizers::tokenize_pieces(text, &self.vocab, |piece, tokens| {
+                cache.tokenize(piece, tokens, |tokens| self.model.tokenize_piece(piece, &self.vocab, tokens))
+            }),
+            None => self.model.tokenize_with_offsets(text, &self.vocab),
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn tokenize_model(&self, text: &str) -> Vec<(TokenId, Range<usize>)> {
+        self.model.tokenize_with_offsets(text, &self.vocab)
+    }
+
+    /// Converts sequence of [`TokenId`] back to text
+    pub fn decode(&self, tokens: &[TokenId]) -> String {
+        self.decoder.decode(tokens, &self.vocab)
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut result = String::new();
+        if self.model.name() != "bpe" {
+            result.push_str(&format!("model {}\n", self.model.name()));
+        }
+        if self.post_processor != PostProcessor::None {
+            result.push_str(&format!("post_processor {}\n", self.post_processor));
+        }
+        if self.decoder != Decoder::Concat {
+            result.push_str(&format!("decoder {}\n", self.decoder));
+        }
+        result + &self.vocab.serialize()
+    }
+
+    /// Reads pipeline file
+    #[cfg(feature = "std")]
+    pub fn load(path: &std::path::Path) -> Result<Self, std::io::Error> {
+        let content = std::fs::read_to_string(path)?;
+        content.parse().map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {e}", path.display()))
+        })
+    }
+}
+
+impl FromStr for TokenizerPipeline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut model = "bpe";
+        let mut post_processor = PostProcessor::default();
+        let mut decoder = Decoder::default();
+
+        let mut vocab = String::with_capacity(s.len());
+        for line in s.lines() {
+            if let Some(name) = line.strip_prefix("model ") {
+                model = name;
+            } else if let Some(x) = line.strip_prefix("post_processor ") {
+                post_processor = x.parse()?;
+            } else if let Some(x) = line.strip_prefix("decoder ") {
+                decoder = x.parse()?;
+            } else {
+                vocab.push_str(line);
+            }
+            // Lines of pipeline settings are left empty, so vocabulary errors have right line numbers
+            vocab.push('\n');
+        }
+
+        let model = model_by_name(model).ok_or_else(|| format!("unknown model {model}"))?;
+        let vocab = vocab.parse::<Vocabulary>().map_err(|e| e.to_string())?;
+        if let Some(token) = post_processor.special_tokens().into_iter().find(|x| !vocab.contains_token(x)) {
+            return Err(format!("post-processor token {token} is not in the vocabulary"));
+        }
+
+        Ok(Self::new(model, vocab)
+            .with_post_processor(post_processor)
+            .with_decoder(decoder))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::VocabularyBuilder;
+
+    use super::*;
+
+    fn vocab() -> Vocabulary {
+        VocabularyBuilder::new()
+            .add_token("[UNK]".to_string(), 0)
+            .add_token("[BOS]".to_string(), 1)
+            .add_token("[EOS]".to_string(), 2)
+            .add_token("a".to_string(), 3)
+            .add_token("b".to_string(), 4)
+            .add_token("ab".to_string(), 5)
+            .build()
+    }
+
+    #[test]
+    fn pipeline() {
+        let pipeline = TokenizerPipeline::new(Box::new(BpeTokenizer::new()), vocab())
+            .with_post_processor(PostProcessor::Wrap { start: "[BOS]".to_string(), end: "[EOS]".to_string() })
+            .with_decoder(Decoder::SkipSpecial);
+
+        assert_eq!(pipeline.tokenize_with_offsets("abc"), vec![(1, 0..0), (5, 0..2), (0, 2..3), (2, 3..3)]);
+        assert_eq!(pipeline.decode(&[1, 5, 0, 3, 2]), "aba");
+        assert_eq!(Decoder::Concat.decode(&[1, 5], pipeline.vocab()), "[BOS]ab");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pipeline_cache() {
+        let mut vocab = vocab();
+        vocab.set_pre_tokenizer(PreTokenizer::Whitespace);
+        let pipeline = TokenizerPipeline::new(Box::new(BpeTokenizer::new()), vocab).with_cache(100);
+
+        let text = "ab ab ba ab";
+        assert_eq!(pipeline.tokenize_with_offsets(text), pipeline.model().tokenize_with_offsets(text, pipeline.vocab()));
+        // Pieces are "ab", " ", "ab", " ", "ba", " ", "ab"
+        let stats = pipeline.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (4, 3));
+    }
+
+    #[test]
+    fn pipeline_file() {
+        let pipeline = TokenizerPipeline::new(Box::new(BpeTokenizer::new()), vocab())
+            .with_post_processor(PostProcessor::Wrap { start: "[BOS]".to_string(), end: "[EOS]".to_string() })
+            .with_decoder(Decoder::SkipSpecial);
+        let content = pipeline.serialize();
+        assert!(content.starts_with("post_processor wrap [BOS] [EOS]\ndecoder skip-special\n0:[UNK]\n"));
+
+        let loaded: TokenizerPipeline = content.parse().unwrap();
+        assert_eq!(loaded.model().name(), "bpe");
+        assert_eq!(loaded.post_processor(), pipeline.post_processor());
+        assert_eq!(loaded.decoder(), Decoder::SkipSpecial);
+        assert_eq!(loaded.vocab().fingerprint(), pipeline.vocab().fingerprint());
+
+        // Plain vocabulary is a pipeline with defaults
+        let plain: TokenizerPipeline = vocab().serialize().parse().unwrap();
+        assert_eq!(plain.post_processor(), &PostProcessor::None);
+        assert_eq!(plain.serialize(), vocab().serialize());
+
+        assert!("model unigram\n0:a\n".parse::<TokenizerPipeline>().is_err());
+        assert!("post_processor wrap [BOS] [X]\n0:[BOS]\n".parse::<TokenizerPipeline>().is_err());
+        let error = "decoder skip-special\n0:a\nx:b\n".parse::<TokenizerPipeline>().err();
+        assert_eq!(error.as_deref(), Some("line 3: invalid token id x"));
+    }
+}
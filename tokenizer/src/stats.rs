@@ -0,0 +1,206 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::collections::HashMap;
+use crate::tokenizers::TokenId;
+use crate::vocab::Vocabulary;
+
+/// Writing system of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Script {
+    Latin,
+    Cyrillic,
+    /// Letters of other scripts, digits, punctuation and whitespace
+    Other,
+}
+
+impl Script {
+    /// Returns script of a character
+    pub fn of_char(ch: char) -> Self {
+        match ch {
+            'A'..='Z' | 'a'..='z' | '\u{AA}' | '\u{BA}'
+            | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{24F}'
+            | '\u{1E00}'..='\u{1EFF}' | '\u{2C60}'..='\u{2C7F}' | '\u{A720}'..='\u{A7FF}'
+            | '\u{FF21}'..='\u{FF3A}' | '\u{FF41}'..='\u{FF5A}' => Script::Latin,
+            '\u{400}'..='\u{52F}' | '\u{1C80}'..='\u{1C8F}'
+            | '\u{2DE0}'..='\u{2DFF}' | '\u{A640}'..='\u{A69F}' => Script::Cyrillic,
+            _ => Script::Other,
+        }
+    }
+
+    /// Returns script of the first Latin or Cyrillic character of text,
+    /// if there is no such character returns [`Script::Other`]
+    pub fn of_str(text: &str) -> Self {
+        text.chars()
+            .map(Script::of_char)
+            .find(|x| *x != Script::Other)
+            .unwrap_or(Script::Other)
+    }
+}
+
+/// Counters of tokenized text
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextStats {
+    pub chars: usize,
+    pub tokens: usize,
+    pub words: usize,
+    /// Count of unknown tokens
+    pub unknown: usize,
+}
+
+impl TextStats {
+    /// Returns average count of characters per token (compression ratio),
+    /// zero if there are no tokens
+    pub fn chars_per_token(&self) -> f64 {
+        ratio(self.chars, self.tokens)
+    }
+
+    /// Returns average count of tokens per word, zero if there are no words
+    pub fn fertility(&self) -> f64 {
+        ratio(self.tokens, self.words)
+    }
+
+    /// Returns share of unknown tokens, zero if there are no tokens
+    pub fn unk_rate(&self) -> f64 {
+        ratio(self.unknown, self.tokens)
+    }
+}
+
+/// Quality report of tokenization.
+///
+/// Tokens are attributed to the script of their first letter and words
+/// (whitespace separated) to the script of their first letter, so
+/// `by_script` shows how well vocabulary serves each language.
+#[derive(Debug, Clone, Default)]
+pub struct TokenizationStats {
+    pub total: TextStats,
+    pub by_script: BTreeMap<Script, TextStats>,
+    /// Token length in characters -> count of tokens
+    pub length_histogram: BTreeMap<usize, usize>,
+    /// Token -> count of occurrences
+    pub token_counts: HashMap<TokenId, usize>,
+}
+
+impl TokenizationStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds text and its tokens to the report.
+    ///
+    /// Token is unknown if its value doesn't match the text,
+    /// every unknown token stands for one character.
+    pub fn add(&mut self, text: &str, tokens: &[TokenId], vocab: &Vocabulary) {
+        let mut rest = text;
+        for token in tokens {
+            *self.token_counts.entry(*token).or_insert(0) += 1;
+
+            let (piece, unknown) = match vocab.get_token(*token) {
+                Some(piece) if !piece.is_empty() && rest.starts_with(piece) => (piece, false),
+                _ => match rest.chars().next() {
+                    Some(ch) => (&rest[..ch.len_utf8()], true),
+                    None => ("", true),
+                },
+            };
+            rest = &rest[piece.len()..];
+
+            let chars = piece.chars().count();
+            *self.length_histogram.entry(chars).or_insert(0) += 1;
+
+            for stats in [&mut self.total, self.by_script.entry(Script::of_str(piece)).or_default()] {
+                stats.chars += chars;
+                stats.tokens += 1;
+                stats.unknown += unknown as usize;
+            }
+        }
+
+        for word in text.split_whitespace() {
+            self.total.words += 1;
+            self.by_script.entry(Script::of_str(word)).or_default().words += 1;
+        }
+    }
+
+    /// Returns [`TokenId`]s of vocabulary which never occurred, sorted
+    pub fn unused_tokens(&self, vocab: &Vocabulary) -> Vec<TokenId> {
+        vocab.iter()
+            .map(|(id, _)| id)
+            .filter(|id| !self.token_counts.contains_key(id))
+            .collect()
+    }
+
+    /// Returns share of vocabulary tokens which occurred at least once,
+    /// zero for empty vocabulary
+    pub fn utilization(&self, vocab: &Vocabulary) -> f64 {
+        let used = vocab.iter().filter(|(id, _)| self.token_counts.contains_key(id)).count();
+        ratio(used, vocab.len())
+    }
+}
+
+/// Returns `a / b`, zero if `b` is zero
+fn ratio(a: usize, b: usize) -> f64 {
+    match b {
+        0 => 0.0,
+        _ => a as f64 / b as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use crate::tokenizers::{BpeTokenizer, Tokenizer};
+    use crate::vocab::VocabularyBuilder;
+
+    use super::*;
+
+    #[test]
+    fn script() {
+        assert_eq!(Script::of_char('a'), Script::Latin);
+        assert_eq!(Script::of_char('é'), Script::Latin);
+        assert_eq!(Script::of_char('ж'), Script::Cyrillic);
+        assert_eq!(Script::of_char('Ё'), Script::Cyrillic);
+        assert_eq!(Script::of_char(' '), Script::Other);
+        assert_eq!(Script::of_str(" \"мир"), Script::Cyrillic);
+        assert_eq!(Script::of_str("1, 2"), Script::Other);
+    }
+
+    #[test]
+    fn stats() {
+        let vocab = VocabularyBuilder::new()
+            .add_token("[UNK]".to_string(), 0)
+            .add_token("a".to_string(), 1)
+            .add_token("b".to_string(), 2)
+            .add_token("ab".to_string(), 3)
+            .add_token(" ".to_string(), 4)
+            .add_token("м".to_string(), 5)
+            .add_token("и".to_string(), 6)
+            .add_token("ми".to_string(), 7)
+            .add_token("unused".to_string(), 8)
+            .build();
+        let text = "abab ab мир";
+        let tokens = BpeTokenizer::new().tokenize(text, &vocab);
+        assert_eq!(tokens, vec![3, 3, 4, 3, 4, 7, 0]);
+
+        let mut stats = TokenizationStats::new();
+        stats.add(text, &tokens, &vocab);
+
+        assert_eq!(stats.total, TextStats { chars: 11, tokens: 7, words: 3, unknown: 1 });
+        assert_eq!(stats.by_script[&Script::Latin], TextStats { chars: 6, tokens: 3, words: 2, unknown: 0 });
+        assert_eq!(stats.by_script[&Script::Cyrillic], TextStats { chars: 3, tokens: 2, words: 1, unknown: 1 });
+        assert_eq!(stats.by_script[&Script::Other], TextStats { chars: 2, tokens: 2, words: 0, unknown: 0 });
+        assert_eq!(stats.length_histogram, BTreeMap::from([(1, 3), (2, 4)]));
+        assert_eq!(stats.unused_tokens(&vocab), vec![1, 2, 5, 6, 8]);
+        assert!((stats.utilization(&vocab) - 4.0 / 9.0).abs() < 1e-9);
+        assert!((stats.total.fertility() - 7.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.by_script[&Script::Other].fertility(), 0.0);
+    }
+
+    #[test]
+    fn empty() {
+        let stats = TokenizationStats::new();
+        assert_eq!(stats.total.chars_per_token(), 0.0);
+        assert_eq!(stats.total.fertility(), 0.0);
+        assert_eq!(stats.total.unk_rate(), 0.0);
+        assert_eq!(stats.utilization(&Vocabulary::new()), 0.0);
+    }
+}
@@ -1,113 +1,329 @@
-use std::collections::HashMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
 
-use crate::vocab::Vocabulary;
+use crate::alphabet::Alphabet;
+use crate::collections::HashMap;
+use crate::corpus::CorpusSource;
+use crate::normalizer::{Normalizer, PreTokenizer};
+use crate::vocab::{Merge, Vocabulary};
 
 /// Token identifier
 pub type TokenId = u32;
 
-pub trait Tokenizer {
-    fn new() -> Self;
+/// Tokenization model.
+///
+/// The trait is object-safe, so models can be chosen at runtime
+/// and used as `Box<dyn Tokenizer>`, see [`crate::TokenizerPipeline`].
+pub trait Tokenizer: Send + Sync {
+    /// Returns name of the model saved into pipeline file
+    fn name(&self) -> &'static str;
 
     /// Fills [`Vocabulary`] using [`TokenId`]s from given corpus
-    fn fill_vocab(&self, corpus: &str, vocab: &mut Vocabulary);
+    fn fill_vocab(&self, corpus: &str, vocab: &mut Vocabulary) {
+        self.fill_vocab_weighted(&[CorpusSource::new(vec![corpus.to_string()], 1.0)], vocab);
+    }
+
+    /// Fills [`Vocabulary`] using [`TokenId`]s from several corpora,
+    /// statistics of each source are scaled by its weight
+    fn fill_vocab_weighted(&self, sources: &[CorpusSource], vocab: &mut Vocabulary);
 
     /// Converts given text to sequence of [`TokenId`]
-    fn tokenize(&self, text: &str, vocab: &Vocabulary) -> Vec<TokenId>;
+    fn tokenize(&self, text: &str, vocab: &Vocabulary) -> Vec<TokenId> {
+        self.tokenize_with_offsets(text, vocab).into_iter().map(|x| x.0).collect()
+    }
+
+    /// Converts given text to sequence of [`TokenId`] with byte ranges
+    /// of tokens in the text.
+    ///
+    /// Text is normalized and split using settings of the vocabulary,
+    /// every piece is tokenized by [`Tokenizer::tokenize_piece`].
+    /// Ranges point to the original text, not the normalized one.
+    fn tokenize_with_offsets(&self, text: &str, vocab: &Vocabulary) -> Vec<(TokenId, Range<usize>)> {
+        tokenize_pieces(text, vocab, |piece, tokens| self.tokenize_piece(piece, vocab, tokens))
+    }
+
+    /// Appends tokens of one normalized and pre-tokenized piece of text,
+    /// ranges are relative to the piece
+    fn tokenize_piece(&self, piece: &str, vocab: &Vocabulary, tokens: &mut Vec<(TokenId, Range<usize>)>);
+
+    /// Converts sequence of [`TokenId`] back to text,
+    /// unknown [`TokenId`]s are skipped
+    fn decode(&self, tokens: &[TokenId], vocab: &Vocabulary) -> String {
+        tokens.iter().filter_map(|x| vocab.get_token(*x)).collect()
+    }
+}
+
+/// Normalizes and splits text using settings of the vocabulary,
+/// `tokenize_piece` appends tokens of every piece with ranges relative to the piece.
+/// Returns tokens with ranges in the original text.
+pub(crate) fn tokenize_pieces(
+    text: &str,
+    vocab: &Vocabulary,
+    mut tokenize_piece: impl FnMut(&str, &mut Vec<(TokenId, Range<usize>)>),
+) -> Vec<(TokenId, Range<usize>)> {
+    let (text, offsets) = vocab.normalizer().normalize_aligned(text);
+
+    let mut tokens = Vec::new();
+    for piece in vocab.pre_tokenizer().split(&text) {
+        let offset = piece.as_ptr() as usize - text.as_ptr() as usize;
+        let start = tokens.len();
+        tokenize_piece(piece, &mut tokens);
+        for (_, range) in tokens[start..].iter_mut() {
+            *range = offsets[offset + range.start]..offsets[offset + range.end];
+        }
+    }
+    tokens
 }
 
 /// Character based BPE tokenizer
+#[derive(Debug, Clone)]
 pub struct BpeTokenizer {
-    pub max_size: usize,
+    max_size: usize,
+
+    /// Minimal weighted frequency of a pair required to merge it
+    min_frequency: u32,
+
+    /// Normalizer used for training, saved into the vocabulary
+    normalizer: Normalizer,
+
+    /// Pre-tokenizer used for training, saved into the vocabulary
+    pre_tokenizer: PreTokenizer,
+
+    /// Units of base tokens, saved into the vocabulary
+    alphabet: Alphabet,
 }
 
-impl Tokenizer for BpeTokenizer {
-    fn new() -> Self {
+impl BpeTokenizer {
+    /// Returns tokenizer with default training settings,
+    /// use [`BpeTokenizerBuilder`] to change them
+    pub fn new() -> Self {
         Self {
             max_size: 100,
+            min_frequency: 2,
+            normalizer: Normalizer::default(),
+            pre_tokenizer: PreTokenizer::default(),
+            alphabet: Alphabet::default(),
         }
     }
 
-    fn fill_vocab(&self, corpus: &str, vocab: &mut Vocabulary) {
-        let mut corpus: Vec<TokenId> = corpus.chars().map(|ch| {
-            if let Some(id) = vocab.try_add_token(ch.to_string()) {
-                id
-            } else {
-                vocab.get_token_id(&ch.to_string()).unwrap()
+    pub fn builder() -> BpeTokenizerBuilder {
+        BpeTokenizerBuilder::new()
+    }
+
+    /// Returns maximum vocabulary size reached by training
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn min_frequency(&self) -> u32 {
+        self.min_frequency
+    }
+
+    pub fn normalizer(&self) -> Normalizer {
+        self.normalizer
+    }
+
+    pub fn pre_tokenizer(&self) -> PreTokenizer {
+        self.pre_tokenizer
+    }
+
+    pub fn alphabet(&self) -> Alphabet {
+        self.alphabet
+    }
+}
+
+impl Default for BpeTokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tokenizer for BpeTokenizer {
+    fn name(&self) -> &'static str {
+        "bpe"
+    }
+
+    /// Merges the most frequent pair of adjacent tokens until the vocabulary
+    /// reaches `max_size` or no pair has frequency of at least `min_frequency`.
+    ///
+    /// Text is normalized and split into pieces by pre-tokenizer, pairs never
+    /// cross piece boundaries, base tokens are units of the alphabet.
+    /// Pair frequency is the sum of pair counts in each source multiplied
+    /// by the source weight, so pairs of sources with weights below 1 need
+    /// more occurrences to pass `min_frequency`.
+    ///
+    /// Training is deterministic: when several pairs share the highest frequency,
+    /// the pair with the smallest `(left, right)` [`TokenId`]s wins, so the same
+    /// corpus always produces the same vocabulary.
+    fn fill_vocab_weighted(&self, sources: &[CorpusSource], vocab: &mut Vocabulary) {
+        vocab.set_normalizer(self.normalizer);
+        vocab.set_pre_tokenizer(self.pre_tokenizer);
+        vocab.set_alphabet(self.alphabet);
+
+        // Equal pieces are trained once: (Piece, weighted count, count)
+        let mut corpus: Vec<(Vec<TokenId>, f64, u32)> = Vec::new();
+        let mut pieces: HashMap<String, usize> = HashMap::new();
+        for source in sources {
+            for document in source.documents.iter() {
+                let document = self.normalizer.normalize(document);
+                for piece in self.pre_tokenizer.split(&document) {
+                    if let Some(i) = pieces.get(piece) {
+                        corpus[*i].1 += source.weight;
+                        corpus[*i].2 += 1;
+                        continue;
+                    }
+
+                    let tokens = self.alphabet.units(piece).map(|unit| {
+                        if let Some(id) = vocab.try_add_token(unit.to_string()) {
+                            id
+                        } else {
+                            vocab.get_token_id(unit).unwrap()
+                        }
+                    }).collect();
+                    pieces.insert(piece.to_string(), corpus.len());
+                    corpus.push((tokens, source.weight, 1));
+                }
             }
-        }).collect();
-        println!("BASE");
+        }
+        drop(pieces);
 
-        let mut freqs: HashMap<(TokenId, TokenId), u32> = HashMap::new();
-        let len = self.max_size - vocab.len();
+        // Pair -> (Weighted frequency, Count)
+        let mut freqs: HashMap<(TokenId, TokenId), (f64, u32)> = HashMap::new();
+        let len = self.max_size.saturating_sub(vocab.len());
         for _ in 0..len {
-            let mut flag = false;
-            {
-                for pair in corpus.windows(2) {
-                    let pair = (pair[0], pair[1]);
-                    if let Some(x) = freqs.get_mut(&pair) {
-                        *x += 1;
-                        flag = true;
-                    } else {
-                        freqs.insert(pair, 1);
-                    }
-                };
+            freqs.clear();
+            for (piece, weight, count) in corpus.iter() {
+                for pair in piece.windows(2) {
+                    let freq = freqs.entry((pair[0], pair[1])).or_insert((0.0, 0));
+                    freq.0 += weight;
+                    freq.1 += count;
+                }
             }
 
-            if !flag {
+            let best = freqs.iter()
+                // Sums of weights like 0.1 may fall just below the minimum due to rounding
+                .filter(|(_, (freq, _))| *freq + 1e-9 >= self.min_frequency as f64 && *freq > 0.0)
+                .max_by(|a, b| a.1.0.total_cmp(&b.1.0).then_with(|| b.0.cmp(a.0)))
+                .map(|(pair, _)| *pair);
+            let Some(best) = best else {
                 break;
-            }
+            };
+            let count = freqs[&best].1;
 
-            if let Some((max, _)) = freqs.iter().max_by_key(|x| x.1) {
-                let token = vocab.get_token(max.0).unwrap().to_string() + vocab.get_token(max.1).unwrap();
-                let token_id = vocab.try_add_token(token).unwrap();
+            let token = vocab.get_token(best.0).unwrap().to_string() + vocab.get_token(best.1).unwrap();
+            let token_id = match vocab.get_token_id(&token) {
+                Some(id) => id,
+                None => vocab.try_add_token(token).unwrap(),
+            };
+            vocab.add_merge(Merge { left: best.0, right: best.1, token: token_id, count });
 
+            for (piece, _, _) in corpus.iter_mut() {
+                if piece.len() < 2 {
+                    continue;
+                }
+                let mut merged = Vec::with_capacity(piece.len());
                 let mut i = 0;
-                while i < corpus.len()-1 {
-                    if (corpus[i], corpus[i+1]) == *max {
-                        corpus.remove(i+1);
-                        corpus.remove(i);
-                        corpus.insert(i, token_id);
+                while i < piece.len() {
+                    if i + 1 < piece.len() && (piece[i], piece[i+1]) == best {
+                        merged.push(token_id);
+                        i += 2;
+                    } else {
+                        merged.push(piece[i]);
+                        i += 1;
                     }
-                    i += 1;
                 }
+                *piece = merged;
             }
-            freqs.clear();
         }
     }
 
-    fn tokenize(&self, text: &str, vocab: &Vocabulary) -> Vec<TokenId> {
-        let mut text: Vec<String> = text.chars().map(|ch| ch.to_string()).collect();
-        
-        let mut i = 0;
-        while i < text.len()-1 {
-            let current = text[i].clone() + &text[i+1];
-
-            if vocab.contains_token(&current) {
-                text.remove(i+1);
-                text.remove(i);
-                text.insert(i, current)
-            } else {
-                i += 1;
+    /// Greedily extends current token with next units of vocabulary alphabet
+    /// while the result is in the vocabulary. Unknown units become `[UNK]` token.
+    fn tokenize_piece(&self, piece: &str, vocab: &Vocabulary, tokens: &mut Vec<(TokenId, Range<usize>)>) {
+        let unk_token = vocab.get_token_id("[UNK]").unwrap_or_default();
+        let alphabet = vocab.alphabet();
+        let mut start = 0;
+        let mut i = alphabet.next_unit_len(piece);
+        while i < piece.len() {
+            let end = i + alphabet.next_unit_len(&piece[i..]);
+            if !vocab.contains_token(&piece[start..end]) {
+                tokens.push((vocab.get_token_id(&piece[start..i]).unwrap_or(unk_token), start..i));
+                start = i;
             }
+            i = end;
+        }
+        if start < piece.len() {
+            tokens.push((vocab.get_token_id(&piece[start..]).unwrap_or(unk_token), start..piece.len()));
         }
+    }
+}
 
-        let unk_token = if let Some(id) = vocab.get_token_id("[UNK]") { id } else { 0 };
-        text.iter().map(|ch| {
-            if let Some(id) = vocab.get_token_id(&ch.to_string()) {
-                id
-            } else {
-                unk_token
-            }
-        }).collect()
+/// Builder of [`BpeTokenizer`] training settings
+#[derive(Debug, Clone, Default)]
+pub struct BpeTokenizerBuilder {
+    tokenizer: BpeTokenizer,
+}
+
+impl BpeTokenizerBuilder {
+    pub fn new() -> Self {
+        Self {
+            tokenizer: BpeTokenizer::new(),
+        }
+    }
+
+    pub fn build(self) -> BpeTokenizer {
+        self.tokenizer
+    }
+
+    /// Sets maximum vocabulary size, `100` by default
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.tokenizer.max_size = max_size;
+        self
+    }
+
+    /// Sets minimal frequency of a pair required to merge it, `2` by default.
+    /// Occurrences are weighted by weights of their corpora
+    pub fn min_frequency(mut self, min_frequency: u32) -> Self {
+        self.tokenizer.min_frequency = min_frequency;
+        self
+    }
+
+    pub fn normalizer(mut self, normalizer: Normalizer) -> Self {
+        self.tokenizer.normalizer = normalizer;
+        self
+    }
+
+    pub fn pre_tokenizer(mut self, pre_tokenizer: PreTokenizer) -> Self {
+        self.tokenizer.pre_tokenizer = pre_tokenizer;
+        self
+    }
+
+    /// Sets units of base tokens, `Alphabet::Chars` by default
+    pub fn alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.tokenizer.alphabet = alphabet;
+        self
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use alloc::boxed::Box;
+
     use crate::vocab::VocabularyBuilder;
 
     use super::*;
 
+    #[test]
+    fn object_safe() {
+        let tokenizer: Box<dyn Tokenizer> = Box::new(BpeTokenizer::new());
+        let mut vocab = Vocabulary::new();
+        tokenizer.fill_vocab("abab", &mut vocab);
+        assert_eq!(tokenizer.name(), "bpe");
+        assert_eq!(tokenizer.tokenize("abab", &vocab), vec![2, 2]);
+    }
+
     #[test]
     fn bpe_fill_vocab1() {
         let tokenizer = BpeTokenizer::new();
@@ -157,5 +373,134 @@ mod tests {
 
         let tokens = tokenizer.tokenize("abc", &vocab);
         assert_eq!(tokens, vec![3, 0]);
+
+        let tokens = tokenizer.tokenize("", &vocab);
+        assert_eq!(tokens, vec![]);
+    }
+
+    #[test]
+    fn bpe_fill_vocab_ties() {
+        let tokenizer = BpeTokenizer::new();
+        let mut vocab = Vocabulary::new();
+
+        // "ab", "bc" and "cd" all occur twice, the pair with smallest ids wins
+        tokenizer.fill_vocab("abcdabcd", &mut vocab);
+        assert_eq!(vocab.get_token(4), Some("ab"));
+        assert_eq!(vocab.get_token(5), Some("cd"));
+        assert_eq!(vocab.merges(), &[
+            Merge { left: 0, right: 1, token: 4, count: 2 },
+            Merge { left: 2, right: 3, token: 5, count: 2 },
+            Merge { left: 4, right: 5, token: 6, count: 2 },
+        ]);
+    }
+
+    #[test]
+    fn bpe_fill_vocab_weighted() {
+        let tokenizer = BpeTokenizer::new();
+        let sources = |weight| [
+            CorpusSource::new(vec!["abababab".to_string()], 1.0),
+            CorpusSource::new(vec!["cdcdcd".to_string()], weight),
+        ];
+
+        let mut vocab = Vocabulary::new();
+        tokenizer.fill_vocab_weighted(&sources(1.0), &mut vocab);
+        assert_eq!(vocab.get_token(4), Some("ab"));
+
+        let mut vocab = Vocabulary::new();
+        tokenizer.fill_vocab_weighted(&sources(2.0), &mut vocab);
+        assert_eq!(vocab.get_token(4), Some("cd"));
+
+        // "cd" occurs 3 times, with weight 0.5 its frequency is below the minimum of 2
+        let mut vocab = Vocabulary::new();
+        tokenizer.fill_vocab_weighted(&sources(0.5), &mut vocab);
+        assert!(vocab.contains_token("ab"));
+        assert!(!vocab.contains_token("cd"));
+
+        let mut vocab = Vocabulary::new();
+        let tenths = CorpusSource::new(vec!["cd".to_string(); 20], 0.1);
+        tokenizer.fill_vocab_weighted(&[tenths], &mut vocab);
+        assert!(vocab.contains_token("cd"));
+    }
+
+    #[test]
+    fn bpe_fill_vocab_documents() {
+        let tokenizer = BpeTokenizer::new();
+        let mut vocab = Vocabulary::new();
+
+        // "ba" occurs only across document boundary
+        tokenizer.fill_vocab_weighted(&[CorpusSource::new(vec!["ab".to_string(), "ab".to_string()], 1.0)], &mut vocab);
+        assert!(vocab.contains_token("ab"));
+        assert!(!vocab.contains_token("ba"));
+        assert!(!vocab.contains_token("abab"));
+    }
+
+    #[test]
+    fn bpe_fill_vocab_options() {
+        let tokenizer = BpeTokenizer::builder()
+            .min_frequency(3)
+            .normalizer(Normalizer { lowercase: true, line_endings: false })
+            .pre_tokenizer(PreTokenizer::Whitespace)
+            .build();
+        let mut vocab = Vocabulary::new();
+
+        tokenizer.fill_vocab("Ab ab AB cd cd", &mut vocab);
+        assert_eq!(vocab.pre_tokenizer(), PreTokenizer::Whitespace);
+        assert!(vocab.contains_token("ab"));
+        assert!(!vocab.contains_token("cd"));
+        assert!(!vocab.contains_token("b "));
+        assert!(!vocab.contains_token("A"));
+
+        assert_eq!(tokenizer.tokenize("AB ab", &vocab), tokenizer.tokenize("ab ab", &vocab));
+    }
+
+    #[test]
+    fn bpe_tokenize_offsets() {
+        let tokenizer = BpeTokenizer::new();
+        let mut vocab = VocabularyBuilder::new()
+            .add_token("[UNK]".to_string(), 0)
+            .add_token("a".to_string(), 1)
+            .add_token("\n".to_string(), 2)
+            .add_token("a\n".to_string(), 3)
+            .build();
+        vocab.set_normalizer(Normalizer { lowercase: true, line_endings: true });
+
+        let tokens = tokenizer.tokenize_with_offsets("A\r\nb", &vocab);
+        assert_eq!(tokens, vec![(3, 0..3), (0, 3..4)]);
+        assert_eq!(tokenizer.decode(&[3, 1, 5], &vocab), "a\na");
+    }
+
+    #[test]
+    fn bpe_graphemes() {
+        let text = "за\u{301}мок зе\u{301}мли";
+        let tokenizer = BpeTokenizer::builder().max_size(0).alphabet(Alphabet::Graphemes).build();
+        let mut vocab = Vocabulary::new();
+        tokenizer.fill_vocab(text, &mut vocab);
+        assert_eq!(vocab.alphabet(), Alphabet::Graphemes);
+        assert!(vocab.contains_token("а\u{301}"));
+        assert!(!vocab.contains_token("\u{301}"));
+
+        // Unknown stressed vowel is one [UNK] token, not a vowel and a mark
+        vocab.add_token("[UNK]".to_string(), 100);
+        let tokens = tokenizer.tokenize_with_offsets("о\u{301}м", &vocab);
+        assert_eq!(tokens, vec![(100, 0..4), (vocab.get_token_id("м").unwrap(), 4..6)]);
+
+        let mut chars = Vocabulary::new();
+        BpeTokenizer::builder().max_size(0).build().fill_vocab(text, &mut chars);
+        assert!(chars.contains_token("\u{301}"));
+        assert!(!chars.contains_token("а\u{301}"));
+    }
+
+    #[test]
+    fn bpe_fill_vocab_deterministic() {
+        let corpus = include_str!("../../content/sh.txt");
+        let tokenizer = BpeTokenizer::builder().max_size(120).build();
+
+        let mut first = Vocabulary::new();
+        tokenizer.fill_vocab(corpus, &mut first);
+        let mut second = Vocabulary::new();
+        tokenizer.fill_vocab(corpus, &mut second);
+
+        assert_eq!(first.len(), 120);
+        assert_eq!(first.serialize().as_bytes(), second.serialize().as_bytes());
     }
 }
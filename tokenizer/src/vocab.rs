@@ -1,13 +1,38 @@
-use std::collections::HashMap;
-
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use crate::alphabet::Alphabet;
+use crate::collections::HashMap;
+use crate::fingerprint::{Fingerprint, Fingerprinter};
+use crate::normalizer::{Normalizer, PreTokenizer};
 use crate::tokenizers::TokenId;
 
-/// Contains token-value pairs
-#[derive(Debug, Clone)]
+/// Pair of tokens merged into a new token during training
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Merge {
+    pub left: TokenId,
+    pub right: TokenId,
+    /// Token produced by the merge
+    pub token: TokenId,
+    /// Count of pair occurrences in training corpus when it was merged
+    pub count: u32,
+}
+
+/// Contains token-value pairs, merges learned during training
+/// and text preprocessing settings used for training
+#[derive(Debug, Clone, Default)]
 pub struct Vocabulary {
     decode: HashMap<TokenId, String>,
     encode: HashMap<String, TokenId>,
     next_token: TokenId,
+    merges: Vec<Merge>,
+    // Token -> rank of the first merge producing it
+    merge_ranks: HashMap<TokenId, usize>,
+    normalizer: Normalizer,
+    pre_tokenizer: PreTokenizer,
+    alphabet: Alphabet,
 }
 
 impl Vocabulary {
@@ -16,9 +41,41 @@ impl Vocabulary {
             decode: HashMap::new(),
             encode: HashMap::new(),
             next_token: 0,
+            merges: Vec::new(),
+            merge_ranks: HashMap::new(),
+            normalizer: Normalizer::default(),
+            pre_tokenizer: PreTokenizer::default(),
+            alphabet: Alphabet::default(),
         }
     }
 
+    /// Returns normalizer which should be applied to text before tokenization
+    pub fn normalizer(&self) -> Normalizer {
+        self.normalizer
+    }
+
+    pub fn set_normalizer(&mut self, normalizer: Normalizer) {
+        self.normalizer = normalizer;
+    }
+
+    /// Returns pre-tokenizer which should split text before tokenization
+    pub fn pre_tokenizer(&self) -> PreTokenizer {
+        self.pre_tokenizer
+    }
+
+    pub fn set_pre_tokenizer(&mut self, pre_tokenizer: PreTokenizer) {
+        self.pre_tokenizer = pre_tokenizer;
+    }
+
+    /// Returns units of text which base tokens were made of
+    pub fn alphabet(&self) -> Alphabet {
+        self.alphabet
+    }
+
+    pub fn set_alphabet(&mut self, alphabet: Alphabet) {
+        self.alphabet = alphabet;
+    }
+
     /// If token is unknown adds token to vocabulary and returns its [`TokenId`],
     /// if token already known returns `None`
     pub fn try_add_token(&mut self, value: String) -> Option<TokenId> {
@@ -31,19 +88,136 @@ impl Vocabulary {
         }
     }
 
-    /// Adds token to vocabulary with given [`TokenId`]
+    /// Adds token to vocabulary with given [`TokenId`].
+    ///
+    /// Token previously stored under this [`TokenId`] and previous
+    /// [`TokenId`] of this token are removed.
     pub fn add_token(&mut self, token: String, id: TokenId) {
-        self.encode.insert(token.clone(), id);
-        self.decode.insert(id, token);
+        if let Some(old_token) = self.decode.insert(id, token.clone()) {
+            if old_token != token {
+                self.encode.remove(&old_token);
+                self.remove_merges(id);
+            }
+        }
+        if let Some(old_id) = self.encode.insert(token, id) {
+            if old_id != id {
+                self.decode.remove(&old_id);
+                self.next_token = self.next_token.min(old_id);
+                self.remove_merges(old_id);
+            }
+        }
 
         while self.decode.contains_key(&self.next_token) {
             self.next_token += 1;
         }
     }
 
+    /// Removes token from vocabulary and returns its [`TokenId`],
+    /// if token is unknown returns `None`
+    pub fn remove_token(&mut self, token: &str) -> Option<TokenId> {
+        let id = self.encode.remove(token)?;
+        self.decode.remove(&id);
+        self.next_token = self.next_token.min(id);
+        self.remove_merges(id);
+        Some(id)
+    }
+
+    /// Removes [`TokenId`] from vocabulary and returns its token,
+    /// if [`TokenId`] is unknown returns `None`
+    pub fn remove_token_id(&mut self, id: TokenId) -> Option<String> {
+        let token = self.decode.remove(&id)?;
+        self.encode.remove(&token);
+        self.next_token = self.next_token.min(id);
+        self.remove_merges(id);
+        Some(token)
+    }
+
+    /// Records merge of two tokens, merges are ranked in order of addition
+    pub fn add_merge(&mut self, merge: Merge) {
+        self.merge_ranks.entry(merge.token).or_insert(self.merges.len());
+        self.merges.push(merge);
+    }
+
+    /// Returns merges sorted by rank
+    pub fn merges(&self) -> &[Merge] {
+        &self.merges
+    }
+
+    /// Returns rank and the first merge producing given token,
+    /// if token wasn't produced by a recorded merge returns `None`
+    pub fn get_merge(&self, token: TokenId) -> Option<(usize, &Merge)> {
+        let rank = *self.merge_ranks.get(&token)?;
+        Some((rank, &self.merges[rank]))
+    }
+
+    // Removes merges using or producing token
+    fn remove_merges(&mut self, token: TokenId) {
+        if self.merges.is_empty() {
+            return;
+        }
+        let merges = core::mem::take(&mut self.merges);
+        self.merge_ranks.clear();
+        for merge in merges {
+            if ![merge.left, merge.right, merge.token].contains(&token) {
+                self.add_merge(merge);
+            }
+        }
+    }
+
+    /// Changes value of token with given [`TokenId`] and returns previous value.
+    ///
+    /// Returns `None` and leaves vocabulary unchanged if [`TokenId`] is unknown
+    /// or new value already belongs to another token.
+    /// Merges using or producing the token are removed, they describe the old value.
+    pub fn rename_token(&mut self, id: TokenId, value: String) -> Option<String> {
+        if !self.decode.contains_key(&id) || self.encode.get(&value).is_some_and(|x| *x != id) {
+            return None;
+        }
+        let old = self.decode.insert(id, value.clone()).unwrap();
+        if old != value {
+            self.remove_merges(id);
+        }
+        self.encode.remove(&old);
+        self.encode.insert(value, id);
+        Some(old)
+    }
+
+    /// Returns iterator over `(TokenId, token)` pairs sorted by [`TokenId`]
+    pub fn iter(&self) -> impl Iterator<Item = (TokenId, &str)> {
+        let mut tokens: Vec<(TokenId, &str)> = self.decode.iter().map(|(id, x)| (*id, x.as_str())).collect();
+        tokens.sort_by_key(|x| x.0);
+        tokens.into_iter()
+    }
+
+    /// Renumbers tokens so [`TokenId`]s are contiguous and start from zero,
+    /// keeping their order. Returns table mapping old ids to new ones.
+    pub fn compact(&mut self) -> IdRemap {
+        let tokens: Vec<(TokenId, String)> = self.iter().map(|(id, x)| (id, x.to_string())).collect();
+        let merges = core::mem::take(&mut self.merges);
+
+        let mut remap = IdRemap::default();
+        *self = Self {
+            normalizer: self.normalizer,
+            pre_tokenizer: self.pre_tokenizer,
+            alphabet: self.alphabet,
+            ..Self::new()
+        };
+        for (new_id, (old_id, token)) in tokens.into_iter().enumerate() {
+            let new_id = new_id as TokenId;
+            self.add_token(token, new_id);
+            remap.map.insert(old_id, new_id);
+        }
+        for merge in merges {
+            if let [Some(left), Some(right), Some(token)] = [merge.left, merge.right, merge.token].map(|x| remap.get(x)) {
+                self.add_merge(Merge { left, right, token, ..merge });
+            }
+        }
+        remap
+    }
+
     /// Returns `true` if vocabulary contains token
     pub fn contains_token(&self, token: &str) -> bool {
-        self.encode.contains_key(&token.to_string())
+        self.encode.contains_key(token)
     }
 
     /// Returns `true` if vocabulary contains [`TokenId`]
@@ -68,45 +242,249 @@ impl Vocabulary {
         self.decode.len()
     }
 
+    /// Returns `true` if vocabulary has no tokens
+    pub fn is_empty(&self) -> bool {
+        self.decode.is_empty()
+    }
+
+    /// Returns stable hash of tokens, their [`TokenId`]s, merges
+    /// and preprocessing settings
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut hasher = Fingerprinter::new();
+        if !self.normalizer.is_identity() {
+            hasher.write_str("normalizer");
+            hasher.write_str(&self.normalizer.to_string());
+        }
+        if self.pre_tokenizer != PreTokenizer::None {
+            hasher.write_str("pre_tokenizer");
+            hasher.write_str(&self.pre_tokenizer.to_string());
+        }
+        if self.alphabet != Alphabet::Chars {
+            hasher.write_str("alphabet");
+            hasher.write_str(&self.alphabet.to_string());
+        }
+        hasher.write_str("tokens");
+        for (id, token) in self.iter() {
+            hasher.write_u32(id);
+            hasher.write_str(token);
+        }
+        if !self.merges.is_empty() {
+            hasher.write_str("merges");
+            for merge in self.merges.iter() {
+                hasher.write_u32(merge.left);
+                hasher.write_u32(merge.right);
+                hasher.write_u32(merge.token);
+                hasher.write_u32(merge.count);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Compares vocabulary with another one
+    pub fn diff(&self, other: &Vocabulary) -> VocabularyDiff {
+        let mut diff = VocabularyDiff::default();
+        for (id, token) in self.iter() {
+            match other.get_token_id(token) {
+                None => diff.only_a.push((id, token.to_string())),
+                Some(other_id) if other_id != id => diff.different_ids.push((token.to_string(), id, other_id)),
+                Some(_) => diff.same += 1,
+            }
+        }
+        for (id, token) in other.iter() {
+            if !self.contains_token(token) {
+                diff.only_b.push((id, token.to_string()));
+            }
+        }
+        diff
+    }
+
+    /// Returns vocabulary with tokens known to both vocabularies,
+    /// tokens and merges keep [`TokenId`]s of this vocabulary
+    pub fn intersect(&self, other: &Vocabulary) -> Vocabulary {
+        let mut result = Self {
+            normalizer: self.normalizer,
+            pre_tokenizer: self.pre_tokenizer,
+            alphabet: self.alphabet,
+            ..Self::new()
+        };
+        for (id, token) in self.iter() {
+            if other.contains_token(token) {
+                result.add_token(token.to_string(), id);
+            }
+        }
+        for merge in self.merges.iter() {
+            if [merge.left, merge.right, merge.token].iter().all(|x| result.contains_token_id(*x)) {
+                result.add_merge(*merge);
+            }
+        }
+        result
+    }
+
+    /// Returns vocabulary with tokens of both vocabularies.
+    ///
+    /// Tokens of this vocabulary keep their [`TokenId`]s, new tokens of
+    /// other vocabulary are appended after the biggest [`TokenId`] in order
+    /// of their [`TokenId`]s in other vocabulary. Merges of other vocabulary
+    /// producing new tokens are appended after merges of this vocabulary.
+    pub fn merge(&self, other: &Vocabulary) -> Vocabulary {
+        let mut result = self.clone();
+        let mut next = self.decode.keys().max().map_or(0, |x| x + 1);
+        for (_, token) in other.iter() {
+            if !result.contains_token(token) {
+                result.add_token(token.to_string(), next);
+                next += 1;
+            }
+        }
+        for merge in other.merges.iter() {
+            let values = [merge.left, merge.right, merge.token].map(|x| other.get_token(x));
+            if values[2].is_some_and(|x| self.contains_token(x)) {
+                continue;
+            }
+            if let [Some(left), Some(right), Some(token)] = values.map(|x| x.and_then(|x| result.get_token_id(x))) {
+                result.add_merge(Merge { left, right, token, ..*merge });
+            }
+        }
+        result
+    }
+
+    /// Serializes vocabulary as `normalizer`, `pre_tokenizer` and `alphabet` settings
+    /// (if they are not default), `id:token` lines sorted by [`TokenId`]
+    /// and `merge left right token count` lines sorted by rank,
+    /// so equal vocabularies always produce identical output
     pub fn serialize(&self) -> String {
         let mut result = String::new();
-
-        for (id, token) in self.decode.iter() {
+        if !self.normalizer.is_identity() {
+            result.push_str(&format!("normalizer {}\n", self.normalizer));
+        }
+        if self.pre_tokenizer != PreTokenizer::None {
+            result.push_str(&format!("pre_tokenizer {}\n", self.pre_tokenizer));
+        }
+        if self.alphabet != Alphabet::Chars {
+            result.push_str(&format!("alphabet {}\n", self.alphabet));
+        }
+        for (id, token) in self.iter() {
             let token = token.replace("\n", "\\n");
             result.push_str(&format!("{id}:{token}\n"));
         }
+        for merge in self.merges.iter() {
+            result.push_str(&format!("merge {} {} {} {}\n", merge.left, merge.right, merge.token, merge.count));
+        }
 
         result
     }
+}
+
+impl FromStr for Vocabulary {
+    type Err = VocabularyError;
 
-    pub fn from_str(content: &str) -> Self {
+    /// Parses vocabulary written by [`Vocabulary::serialize`], empty lines are skipped
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut result = Self::new();
 
-        content.lines().for_each(|x| {
-            let parts = x.split_once(":");
-            if let Some((a, b)) = parts {
-                let b = b.replace("\\n", "\n");
-                result.add_token(b.to_string(), a.parse::<TokenId>().unwrap());
+        for (i, x) in s.lines().enumerate() {
+            let error = |reason: String| VocabularyError { line: i + 1, reason };
+            if x.is_empty() {
+                continue;
+            }
+            if let Some(normalizer) = x.strip_prefix("normalizer ") {
+                result.normalizer = normalizer.parse().map_err(error)?;
+            } else if let Some(pre_tokenizer) = x.strip_prefix("pre_tokenizer ") {
+                result.pre_tokenizer = pre_tokenizer.parse().map_err(error)?;
+            } else if let Some(alphabet) = x.strip_prefix("alphabet ") {
+                result.alphabet = alphabet.parse().map_err(error)?;
+            } else if let Some(merge) = x.strip_prefix("merge ") {
+                let parts: Vec<Option<u32>> = merge.split(' ').map(|x| x.parse().ok()).collect();
+                let [Some(left), Some(right), Some(token), Some(count)] = parts[..] else {
+                    return Err(error(format!("invalid merge {merge}")));
+                };
+                result.add_merge(Merge { left, right, token, count });
+            } else if let Some((id, token)) = x.split_once(":") {
+                let id = id.parse().map_err(|_| error(format!("invalid token id {id}")))?;
+                result.add_token(token.replace("\\n", "\n"), id);
             } else {
-                println!("Error: {}", x);
+                return Err(error(format!("expected id:token, got {x}")));
             }
-        });
+        }
 
-        result
+        Ok(result)
     }
 }
 
-impl std::fmt::Display for Vocabulary {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut tokens: Vec<(TokenId, &str)> = self.decode.iter().map(|(a, x)| (*a, x.as_str())).collect();        
-        tokens.sort_by_key(|x| x.0);
-        for (id, token) in tokens {
-            write!(f, "{} = \"{}\"\n", id, token)?;
+/// Error returned when vocabulary file is malformed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VocabularyError {
+    /// Number of the malformed line, starting from 1
+    pub line: usize,
+
+    pub reason: String,
+}
+
+impl core::fmt::Display for VocabularyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl core::error::Error for VocabularyError {}
+
+impl core::fmt::Display for Vocabulary {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (id, token) in self.iter() {
+            writeln!(f, "{} = \"{}\"", id, token)?;
         }
         Ok(())
     }
 }
 
+/// Differences between vocabularies A and B, produced by [`Vocabulary::diff`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VocabularyDiff {
+    /// Tokens known only to A, sorted by [`TokenId`]
+    pub only_a: Vec<(TokenId, String)>,
+
+    /// Tokens known only to B, sorted by [`TokenId`]
+    pub only_b: Vec<(TokenId, String)>,
+
+    /// Shared tokens with different [`TokenId`]s as `(token, A id, B id)`
+    pub different_ids: Vec<(String, TokenId, TokenId)>,
+
+    /// Count of shared tokens with equal [`TokenId`]s
+    pub same: usize,
+}
+
+impl VocabularyDiff {
+    /// Returns `true` if vocabularies have the same tokens with the same [`TokenId`]s
+    pub fn is_empty(&self) -> bool {
+        self.only_a.is_empty() && self.only_b.is_empty() && self.different_ids.is_empty()
+    }
+}
+
+/// Mapping from old to new [`TokenId`]s, produced by [`Vocabulary::compact`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IdRemap {
+    map: HashMap<TokenId, TokenId>,
+}
+
+impl IdRemap {
+    /// Returns new [`TokenId`] for old one,
+    /// if token was removed returns `None`
+    pub fn get(&self, id: TokenId) -> Option<TokenId> {
+        self.map.get(&id).cloned()
+    }
+
+    /// Converts sequence of old [`TokenId`]s to new ones,
+    /// removed tokens are skipped
+    pub fn apply(&self, tokens: &[TokenId]) -> Vec<TokenId> {
+        tokens.iter().filter_map(|x| self.get(*x)).collect()
+    }
+
+    /// Returns `true` if every [`TokenId`] is mapped to itself
+    pub fn is_identity(&self) -> bool {
+        self.map.iter().all(|(old, new)| old == new)
+    }
+}
+
+#[derive(Default)]
 pub struct VocabularyBuilder {
     vocab: Vocabulary,
 }
@@ -171,6 +549,215 @@ mod tests {
         assert_eq!(vocab.get_token_id("hellow"), Some(1));
     } 
 
+    #[test]
+    fn add_token_replaces() {
+        let mut vocab = Vocabulary::new();
+        vocab.add_token("hellow".to_string(), 0);
+        vocab.add_token("aboba".to_string(), 0);
+        assert_eq!(vocab.get_token_id("hellow"), None);
+        assert_eq!(vocab.get_token(0), Some("aboba"));
+
+        vocab.add_token("aboba".to_string(), 3);
+        assert_eq!(vocab.get_token(0), None);
+        assert_eq!(vocab.get_token(3), Some("aboba"));
+        assert_eq!(vocab.len(), 1);
+        assert_eq!(vocab.try_add_token("world".to_string()), Some(0));
+    }
+
+    #[test]
+    fn remove_token() {
+        let mut vocab = VocabularyBuilder::new()
+            .try_add_token("hellow".to_string())
+            .try_add_token("world".to_string())
+            .try_add_token("aboba".to_string())
+            .build();
+
+        assert_eq!(vocab.remove_token("world"), Some(1));
+        assert_eq!(vocab.remove_token("world"), None);
+        assert_eq!(vocab.remove_token_id(2), Some("aboba".to_string()));
+        assert_eq!(vocab.remove_token_id(2), None);
+        assert!(!vocab.contains_token("aboba"));
+        assert_eq!(vocab.len(), 1);
+
+        assert_eq!(vocab.try_add_token("lol".to_string()), Some(1));
+    }
+
+    #[test]
+    fn rename_token() {
+        let mut vocab = VocabularyBuilder::new()
+            .try_add_token("hellow".to_string())
+            .try_add_token("world".to_string())
+            .build();
+
+        assert_eq!(vocab.rename_token(0, "hello".to_string()), Some("hellow".to_string()));
+        assert_eq!(vocab.get_token(0), Some("hello"));
+        assert_eq!(vocab.get_token_id("hello"), Some(0));
+        assert!(!vocab.contains_token("hellow"));
+
+        assert_eq!(vocab.rename_token(0, "world".to_string()), None);
+        assert_eq!(vocab.rename_token(5, "lol".to_string()), None);
+        assert_eq!(vocab.get_token(0), Some("hello"));
+        assert!(!vocab.contains_token_id(5));
+
+        let mut vocab = VocabularyBuilder::new()
+            .try_add_token("a".to_string())
+            .try_add_token("b".to_string())
+            .try_add_token("ab".to_string())
+            .try_add_token("c".to_string())
+            .try_add_token("cb".to_string())
+            .build();
+        vocab.add_merge(Merge { left: 0, right: 1, token: 2, count: 3 });
+        vocab.add_merge(Merge { left: 3, right: 1, token: 4, count: 2 });
+        let fingerprint = vocab.fingerprint();
+
+        // Renaming to the same value keeps merges
+        assert_eq!(vocab.rename_token(2, "ab".to_string()), Some("ab".to_string()));
+        assert_eq!(vocab.merges().len(), 2);
+        assert_eq!(vocab.fingerprint(), fingerprint);
+
+        assert_eq!(vocab.rename_token(2, "x".to_string()), Some("ab".to_string()));
+        assert_eq!(vocab.merges(), &[Merge { left: 3, right: 1, token: 4, count: 2 }]);
+        assert_eq!(vocab.get_merge(2), None);
+        assert_eq!(vocab.get_merge(4).map(|x| x.0), Some(0));
+    }
+
+    #[test]
+    fn compact() {
+        let mut vocab = VocabularyBuilder::new()
+            .add_token("hellow".to_string(), 0)
+            .add_token("world".to_string(), 2)
+            .add_token("aboba".to_string(), 5)
+            .add_token("lol".to_string(), 7)
+            .build();
+        vocab.remove_token("aboba");
+
+        let remap = vocab.compact();
+        assert_eq!(vocab.iter().collect::<Vec<_>>(), vec![(0, "hellow"), (1, "world"), (2, "lol")]);
+        assert_eq!(remap.get(0), Some(0));
+        assert_eq!(remap.get(2), Some(1));
+        assert_eq!(remap.get(5), None);
+        assert_eq!(remap.get(7), Some(2));
+        assert_eq!(remap.apply(&[7, 5, 2, 0]), vec![2, 1, 0]);
+        assert!(!remap.is_identity());
+        assert!(vocab.compact().is_identity());
+    }
+
+    #[test]
+    fn diff() {
+        let a = VocabularyBuilder::new()
+            .add_token("hellow".to_string(), 0)
+            .add_token("world".to_string(), 1)
+            .add_token("aboba".to_string(), 2)
+            .build();
+        let b = VocabularyBuilder::new()
+            .add_token("hellow".to_string(), 0)
+            .add_token("aboba".to_string(), 1)
+            .add_token("lol".to_string(), 2)
+            .build();
+
+        let diff = a.diff(&b);
+        assert_eq!(diff.only_a, vec![(1, "world".to_string())]);
+        assert_eq!(diff.only_b, vec![(2, "lol".to_string())]);
+        assert_eq!(diff.different_ids, vec![("aboba".to_string(), 2, 1)]);
+        assert_eq!(diff.same, 1);
+        assert!(!diff.is_empty());
+        assert!(a.diff(&a.clone()).is_empty());
+
+        let shared = a.intersect(&b);
+        assert_eq!(shared.iter().collect::<Vec<_>>(), vec![(0, "hellow"), (2, "aboba")]);
+    }
+
+    #[test]
+    fn merge() {
+        let a = VocabularyBuilder::new()
+            .add_token("hellow".to_string(), 0)
+            .add_token("world".to_string(), 3)
+            .build();
+        let b = VocabularyBuilder::new()
+            .add_token("lol".to_string(), 0)
+            .add_token("world".to_string(), 1)
+            .add_token("foo".to_string(), 2)
+            .build();
+
+        let merged = a.merge(&b);
+        assert_eq!(merged.iter().collect::<Vec<_>>(), vec![(0, "hellow"), (3, "world"), (4, "lol"), (5, "foo")]);
+    }
+
+    #[test]
+    fn fingerprint() {
+        let a = VocabularyBuilder::new()
+            .add_token("hellow".to_string(), 0)
+            .add_token("world".to_string(), 1)
+            .build();
+        let b = VocabularyBuilder::new()
+            .add_token("world".to_string(), 1)
+            .add_token("hellow".to_string(), 0)
+            .build();
+        let c = VocabularyBuilder::new()
+            .add_token("hellow".to_string(), 1)
+            .add_token("world".to_string(), 0)
+            .build();
+
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_ne!(a.fingerprint(), c.fingerprint());
+        assert_eq!(a.fingerprint().to_string(), "2d79591840fc1d1e");
+        assert_eq!(a.fingerprint().to_string().parse(), Ok(a.fingerprint()));
+        assert!(a.fingerprint().verify(b.fingerprint()).is_ok());
+        assert!(a.fingerprint().verify(c.fingerprint()).is_err());
+    }
+
+    #[test]
+    fn merges() {
+        let mut vocab = VocabularyBuilder::new()
+            .try_add_token("a".to_string())
+            .try_add_token("b".to_string())
+            .try_add_token("ab".to_string())
+            .try_add_token("abb".to_string())
+            .build();
+        let fingerprint = vocab.fingerprint();
+        vocab.add_merge(Merge { left: 0, right: 1, token: 2, count: 5 });
+        vocab.add_merge(Merge { left: 2, right: 1, token: 3, count: 2 });
+        assert_ne!(vocab.fingerprint(), fingerprint);
+
+        assert_eq!(vocab.get_merge(3), Some((1, &Merge { left: 2, right: 1, token: 3, count: 2 })));
+        assert_eq!(vocab.get_merge(0), None);
+
+        let loaded = vocab.serialize().parse::<Vocabulary>().unwrap();
+        assert_eq!(loaded.merges(), vocab.merges());
+        assert_eq!(loaded.fingerprint(), vocab.fingerprint());
+
+        let fingerprint = vocab.fingerprint();
+        vocab.set_normalizer(Normalizer { lowercase: true, line_endings: false });
+        vocab.set_pre_tokenizer(PreTokenizer::Words);
+        vocab.set_alphabet(Alphabet::Graphemes);
+        assert_ne!(vocab.fingerprint(), fingerprint);
+        let loaded = vocab.serialize().parse::<Vocabulary>().unwrap();
+        assert_eq!(loaded.normalizer(), vocab.normalizer());
+        assert_eq!(loaded.pre_tokenizer(), PreTokenizer::Words);
+        assert_eq!(loaded.alphabet(), Alphabet::Graphemes);
+        assert_eq!(loaded.fingerprint(), vocab.fingerprint());
+
+        vocab.remove_token("a");
+        assert_eq!(vocab.merges(), &[Merge { left: 2, right: 1, token: 3, count: 2 }]);
+        vocab.compact();
+        assert_eq!(vocab.pre_tokenizer(), PreTokenizer::Words);
+        assert_eq!(vocab.get_merge(2), Some((0, &Merge { left: 1, right: 0, token: 2, count: 2 })));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |content: &str| content.parse::<Vocabulary>().unwrap_err();
+        assert_eq!(error("0:a\nx:b\n"), VocabularyError { line: 2, reason: "invalid token id x".to_string() });
+        assert_eq!(error("0:a\n1:b\nmerge 0 1\n").line, 3);
+        assert_eq!(error("normalizer shout\n").line, 1);
+        assert_eq!(error("pre_tokenizer x\n").line, 1);
+        assert_eq!(error("0:a\n\nalphabet x\n").line, 3);
+        assert_eq!(error("0:a\nb\n").to_string(), "line 2: expected id:token, got b");
+
+        let vocab: Vocabulary = "0:a\n\n1:b:c\n".parse().unwrap();
+        assert_eq!(vocab.get_token(1), Some("b:c"));
+    }
+
     #[test]
     fn builder1() {
         let vocab = VocabularyBuilder::new()
@@ -0,0 +1,106 @@
+//! Generates C header from exported items of `src/lib.rs`
+
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+
+    let source = std::fs::read_to_string("src/lib.rs").unwrap();
+    let header = generate_header(&source);
+
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("tokenizer.h"), header).unwrap();
+}
+
+fn generate_header(source: &str) -> String {
+    let mut result = String::from(
+        "/* Generated by build.rs from src/lib.rs, don't edit */\n\n\
+         #ifndef TOKENIZER_H\n#define TOKENIZER_H\n\n\
+         #include <stddef.h>\n#include <stdint.h>\n\n\
+         #ifdef __cplusplus\nextern \"C\" {\n#endif\n\n",
+    );
+
+    let mut docs: Vec<&str> = Vec::new();
+    for line in source.lines() {
+        // Items of test module aren't exported
+        if line.starts_with("#[cfg(test)]") {
+            break;
+        }
+
+        if let Some(doc) = line.strip_prefix("///") {
+            docs.push(doc);
+            continue;
+        }
+        if line.starts_with("#[") {
+            continue;
+        }
+
+        let item = if let Some(constant) = line.strip_prefix("pub const ") {
+            let (name, value) = constant.split_once(':').unwrap();
+            let value = value.split_once('=').unwrap().1.trim().trim_end_matches(';');
+            Some(format!("#define {name} {value}\n"))
+        } else if let Some(name) = line.strip_prefix("pub struct ") {
+            let name = name.trim_end_matches(" {");
+            Some(format!("\ntypedef struct {name} {name};\n"))
+        } else {
+            line.strip_prefix("pub unsafe extern \"C\" fn ")
+                .map(|function| format!("\n{}\n", c_function(function)))
+        };
+
+        if let Some(item) = item {
+            if item.starts_with('\n') {
+                result.push('\n');
+            }
+            for doc in docs.iter() {
+                result.push_str(&format!("//{doc}\n"));
+            }
+            result.push_str(item.trim_start_matches('\n'));
+        }
+        if !line.trim().is_empty() {
+            docs.clear();
+        }
+    }
+
+    result.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+    result
+}
+
+/// Converts `name(arg: Type, ...) -> Type {` to C declaration
+fn c_function(signature: &str) -> String {
+    let (name, rest) = signature.split_once('(').unwrap();
+    let (args, rest) = rest.split_once(')').unwrap();
+    let ret = rest.trim().trim_end_matches('{').trim().strip_prefix("-> ").map_or("void".to_string(), c_type);
+
+    let args: Vec<String> = args.split(", ")
+        .filter(|x| !x.is_empty())
+        .map(|arg| {
+            let (name, ty) = arg.split_once(": ").unwrap();
+            format!("{} {name}", c_type(ty))
+        })
+        .collect();
+    let args = if args.is_empty() { "void".to_string() } else { args.join(", ") };
+
+    format!("{ret} {name}({args});")
+}
+
+fn c_type(ty: &str) -> String {
+    let ty = ty.trim();
+    if let Some(inner) = ty.strip_prefix("*const ") {
+        return match inner.starts_with('*') {
+            true => format!("{}* const", c_type(inner)),
+            false => format!("const {}*", c_type(inner)),
+        };
+    }
+    if let Some(inner) = ty.strip_prefix("*mut ") {
+        return format!("{}*", c_type(inner));
+    }
+    match ty {
+        "c_char" => "char",
+        "u8" => "uint8_t",
+        "u32" => "uint32_t",
+        "i32" => "int32_t",
+        "u64" => "uint64_t",
+        "usize" => "size_t",
+        x => x,
+    }.to_string()
+}
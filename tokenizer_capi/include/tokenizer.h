@@ -0,0 +1,82 @@
+/* Generated by build.rs from src/lib.rs, don't edit */
+
+#ifndef TOKENIZER_H
+#define TOKENIZER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TOKENIZER_OK 0
+#define TOKENIZER_NULL_POINTER 1
+#define TOKENIZER_INVALID_UTF8 2
+#define TOKENIZER_IO_ERROR 3
+#define TOKENIZER_PANIC 4
+#define TOKENIZER_PARSE_ERROR 5
+
+// Loaded tokenizer pipeline, opaque for C
+typedef struct TokenizerVocab TokenizerVocab;
+
+// Returns message of the last error on this thread, valid until the next failed call.
+//
+// # Safety
+// Returned string shouldn't be freed
+const char* tokenizer_last_error(void);
+
+// Loads vocabulary or pipeline file, result should be freed with `tokenizer_vocab_free`.
+//
+// # Safety
+// `path` should be a NUL-terminated string, `out` should be writable
+int32_t tokenizer_vocab_load(const char* path, TokenizerVocab** out);
+
+// Parses vocabulary from `len` bytes of file content, result should be freed with `tokenizer_vocab_free`.
+//
+// # Safety
+// `data` should point to `len` readable bytes, `out` should be writable
+int32_t tokenizer_vocab_parse(const char* data, size_t len, TokenizerVocab** out);
+
+// Frees vocabulary, null is ignored.
+//
+// # Safety
+// `vocab` should be returned by `tokenizer_vocab_load` or `tokenizer_vocab_parse` and not freed before
+void tokenizer_vocab_free(TokenizerVocab* vocab);
+
+// Writes count of tokens in vocabulary to `out`.
+//
+// # Safety
+// `vocab` should be a live vocabulary, `out` should be writable
+int32_t tokenizer_vocab_size(const TokenizerVocab* vocab, size_t* out);
+
+// Encodes `len` bytes of UTF-8 text, ids should be freed with `tokenizer_ids_free`.
+//
+// # Safety
+// `vocab` should be a live vocabulary, `text` should point to `len` readable bytes, `ids` and `ids_len` should be writable
+int32_t tokenizer_encode(const TokenizerVocab* vocab, const char* text, size_t len, uint32_t** ids, size_t* ids_len);
+
+// Frees ids returned by `tokenizer_encode`, null is ignored.
+//
+// # Safety
+// `ids` and `len` should be returned by `tokenizer_encode` and not freed before
+void tokenizer_ids_free(uint32_t* ids, size_t len);
+
+// Decodes `len` ids to NUL-terminated UTF-8 text of `text_len` bytes using decoder of the pipeline.
+// Text should be freed with `tokenizer_string_free`.
+//
+// # Safety
+// `vocab` should be a live vocabulary, `ids` should point to `len` ids, `text` and `text_len` should be writable
+int32_t tokenizer_decode(const TokenizerVocab* vocab, const uint32_t* ids, size_t len, char** text, size_t* text_len);
+
+// Frees text returned by `tokenizer_decode`, null is ignored.
+//
+// # Safety
+// `text` and `len` should be returned by `tokenizer_decode` and not freed before
+void tokenizer_string_free(char* text, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
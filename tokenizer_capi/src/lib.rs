@@ -0,0 +1,288 @@
+//! C API of the tokenizer.
+//!
+//! Every function returns a status code, `TOKENIZER_OK` on success.
+//! On failure message of the last error of the calling thread
+//! is returned by `tokenizer_last_error`.
+//!
+//! Header `include/tokenizer.h` is generated by `build.rs` from this file,
+//! so signatures of exported functions must fit on one line.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use tokenizer::tokenizers::TokenId;
+use tokenizer::TokenizerPipeline;
+
+pub const TOKENIZER_OK: i32 = 0;
+pub const TOKENIZER_NULL_POINTER: i32 = 1;
+pub const TOKENIZER_INVALID_UTF8: i32 = 2;
+pub const TOKENIZER_IO_ERROR: i32 = 3;
+pub const TOKENIZER_PANIC: i32 = 4;
+pub const TOKENIZER_PARSE_ERROR: i32 = 5;
+
+/// Loaded tokenizer pipeline, opaque for C
+pub struct TokenizerVocab {
+    pipeline: TokenizerPipeline,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+struct Error {
+    status: i32,
+    message: String,
+}
+
+impl Error {
+    fn new(status: i32, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn null(name: &str) -> Self {
+        Self::new(TOKENIZER_NULL_POINTER, format!("{name} is null"))
+    }
+}
+
+/// Runs `f` catching panics and storing error message
+fn guard(f: impl FnOnce() -> Result<(), Error>) -> i32 {
+    let error = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return TOKENIZER_OK,
+        Ok(Err(e)) => e,
+        Err(_) => Error::new(TOKENIZER_PANIC, "tokenizer panicked"),
+    };
+
+    let message = CString::new(error.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|x| *x.borrow_mut() = message);
+    error.status
+}
+
+/// # Safety
+/// `data` should be null or point to `len` readable bytes
+unsafe fn bytes<'a>(data: *const c_char, len: usize, name: &str) -> Result<&'a [u8], Error> {
+    if data.is_null() {
+        if len == 0 {
+            return Ok(&[]);
+        }
+        return Err(Error::null(name));
+    }
+    Ok(std::slice::from_raw_parts(data as *const u8, len))
+}
+
+/// # Safety
+/// `data` should be null or point to `len` readable bytes
+unsafe fn utf8<'a>(data: *const c_char, len: usize, name: &str) -> Result<&'a str, Error> {
+    std::str::from_utf8(bytes(data, len, name)?)
+        .map_err(|e| Error::new(TOKENIZER_INVALID_UTF8, format!("{name} is not valid UTF-8: {e}")))
+}
+
+fn into_vocab(content: &str, out: *mut *mut TokenizerVocab) -> Result<(), Error> {
+    let pipeline = content.parse().map_err(|e| Error::new(TOKENIZER_PARSE_ERROR, e))?;
+    let vocab = Box::new(TokenizerVocab {
+        pipeline,
+    });
+    // SAFETY: callers check that `out` isn't null
+    unsafe { *out = Box::into_raw(vocab) };
+    Ok(())
+}
+
+/// Returns message of the last error on this thread, valid until the next failed call.
+///
+/// # Safety
+/// Returned string shouldn't be freed
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_last_error() -> *const c_char {
+    LAST_ERROR.with(|x| x.borrow().as_ptr())
+}
+
+/// Loads vocabulary or pipeline file, result should be freed with `tokenizer_vocab_free`.
+///
+/// # Safety
+/// `path` should be a NUL-terminated string, `out` should be writable
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_vocab_load(path: *const c_char, out: *mut *mut TokenizerVocab) -> i32 {
+    guard(|| {
+        if path.is_null() {
+            return Err(Error::null("path"));
+        }
+        if out.is_null() {
+            return Err(Error::null("out"));
+        }
+        let path = CStr::from_ptr(path)
+            .to_str()
+            .map_err(|e| Error::new(TOKENIZER_INVALID_UTF8, format!("path is not valid UTF-8: {e}")))?;
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| Error::new(TOKENIZER_IO_ERROR, format!("{path}: {e}")))?;
+
+        into_vocab(&content, out)
+    })
+}
+
+/// Parses vocabulary from `len` bytes of file content, result should be freed with `tokenizer_vocab_free`.
+///
+/// # Safety
+/// `data` should point to `len` readable bytes, `out` should be writable
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_vocab_parse(data: *const c_char, len: usize, out: *mut *mut TokenizerVocab) -> i32 {
+    guard(|| {
+        let content = utf8(data, len, "data")?;
+        if out.is_null() {
+            return Err(Error::null("out"));
+        }
+
+        into_vocab(content, out)
+    })
+}
+
+/// Frees vocabulary, null is ignored.
+///
+/// # Safety
+/// `vocab` should be returned by `tokenizer_vocab_load` or `tokenizer_vocab_parse` and not freed before
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_vocab_free(vocab: *mut TokenizerVocab) {
+    if !vocab.is_null() {
+        drop(Box::from_raw(vocab));
+    }
+}
+
+/// Writes count of tokens in vocabulary to `out`.
+///
+/// # Safety
+/// `vocab` should be a live vocabulary, `out` should be writable
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_vocab_size(vocab: *const TokenizerVocab, out: *mut usize) -> i32 {
+    guard(|| {
+        let vocab = vocab.as_ref().ok_or_else(|| Error::null("vocab"))?;
+        if out.is_null() {
+            return Err(Error::null("out"));
+        }
+        *out = vocab.pipeline.vocab().len();
+        Ok(())
+    })
+}
+
+/// Encodes `len` bytes of UTF-8 text, ids should be freed with `tokenizer_ids_free`.
+///
+/// # Safety
+/// `vocab` should be a live vocabulary, `text` should point to `len` readable bytes, `ids` and `ids_len` should be writable
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_encode(vocab: *const TokenizerVocab, text: *const c_char, len: usize, ids: *mut *mut u32, ids_len: *mut usize) -> i32 {
+    guard(|| {
+        let vocab = vocab.as_ref().ok_or_else(|| Error::null("vocab"))?;
+        let text = utf8(text, len, "text")?;
+        if ids.is_null() || ids_len.is_null() {
+            return Err(Error::null("ids"));
+        }
+
+        let tokens: Box<[TokenId]> = vocab.pipeline.tokenize(text).into_boxed_slice();
+        *ids_len = tokens.len();
+        *ids = Box::into_raw(tokens) as *mut u32;
+        Ok(())
+    })
+}
+
+/// Frees ids returned by `tokenizer_encode`, null is ignored.
+///
+/// # Safety
+/// `ids` and `len` should be returned by `tokenizer_encode` and not freed before
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_ids_free(ids: *mut u32, len: usize) {
+    if !ids.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ids, len)));
+    }
+}
+
+/// Decodes `len` ids to NUL-terminated UTF-8 text of `text_len` bytes using decoder of the pipeline.
+/// Text should be freed with `tokenizer_string_free`.
+///
+/// # Safety
+/// `vocab` should be a live vocabulary, `ids` should point to `len` ids, `text` and `text_len` should be writable
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_decode(vocab: *const TokenizerVocab, ids: *const u32, len: usize, text: *mut *mut c_char, text_len: *mut usize) -> i32 {
+    guard(|| {
+        let vocab = vocab.as_ref().ok_or_else(|| Error::null("vocab"))?;
+        if ids.is_null() && len != 0 {
+            return Err(Error::null("ids"));
+        }
+        if text.is_null() || text_len.is_null() {
+            return Err(Error::null("text"));
+        }
+        let ids = if len == 0 { &[] } else { std::slice::from_raw_parts(ids, len) };
+
+        let mut bytes = vocab.pipeline.decode(ids).into_bytes();
+        *text_len = bytes.len();
+        bytes.push(0);
+        *text = Box::into_raw(bytes.into_boxed_slice()) as *mut c_char;
+        Ok(())
+    })
+}
+
+/// Frees text returned by `tokenizer_decode`, null is ignored.
+///
+/// # Safety
+/// `text` and `len` should be returned by `tokenizer_decode` and not freed before
+#[no_mangle]
+pub unsafe extern "C" fn tokenizer_string_free(text: *mut c_char, len: usize) {
+    if !text.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(text as *mut u8, len + 1)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let content = "0:[UNK]\n1:a\n2:b\n3:ab\n4: \n";
+        unsafe {
+            let mut vocab = null_mut();
+            assert_eq!(tokenizer_vocab_parse(content.as_ptr() as _, content.len(), &mut vocab), TOKENIZER_OK);
+            let mut size = 0;
+            assert_eq!(tokenizer_vocab_size(vocab, &mut size), TOKENIZER_OK);
+            assert_eq!(size, 5);
+
+            let text = "ab a";
+            let (mut ids, mut ids_len) = (null_mut(), 0);
+            assert_eq!(tokenizer_encode(vocab, text.as_ptr() as _, text.len(), &mut ids, &mut ids_len), TOKENIZER_OK);
+            assert_eq!(std::slice::from_raw_parts(ids, ids_len), &[3, 4, 1]);
+
+            let (mut decoded, mut decoded_len) = (null_mut(), 0);
+            assert_eq!(tokenizer_decode(vocab, ids, ids_len, &mut decoded, &mut decoded_len), TOKENIZER_OK);
+            assert_eq!(CStr::from_ptr(decoded).to_str().unwrap(), text);
+            assert_eq!(decoded_len, text.len());
+
+            tokenizer_string_free(decoded, decoded_len);
+            tokenizer_ids_free(ids, ids_len);
+            tokenizer_vocab_free(vocab);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        unsafe {
+            let mut vocab = null_mut();
+            let path = CString::new("/nonexistent/vocab.vcb").unwrap();
+            assert_eq!(tokenizer_vocab_load(path.as_ptr(), &mut vocab), TOKENIZER_IO_ERROR);
+            assert!(CStr::from_ptr(tokenizer_last_error()).to_str().unwrap().starts_with("/nonexistent/vocab.vcb: "));
+            assert!(vocab.is_null());
+
+            let bytes = [0xff_u8, 0xfe];
+            assert_eq!(tokenizer_vocab_parse(bytes.as_ptr() as _, bytes.len(), &mut vocab), TOKENIZER_INVALID_UTF8);
+            let content = "model unknown\n";
+            assert_eq!(tokenizer_vocab_parse(content.as_ptr() as _, content.len(), &mut vocab), TOKENIZER_PARSE_ERROR);
+            let content = "0:a\nx:foo\n";
+            assert_eq!(tokenizer_vocab_parse(content.as_ptr() as _, content.len(), &mut vocab), TOKENIZER_PARSE_ERROR);
+            assert_eq!(CStr::from_ptr(tokenizer_last_error()).to_str().unwrap(), "line 2: invalid token id x");
+            assert!(vocab.is_null());
+            assert_eq!(tokenizer_vocab_size(null_mut(), &mut 0), TOKENIZER_NULL_POINTER);
+            assert_eq!(CStr::from_ptr(tokenizer_last_error()).to_str().unwrap(), "vocab is null");
+        }
+    }
+}
@@ -0,0 +1,62 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "tokenizer.h"
+
+#define CHECK(call)                                                              \
+    do {                                                                         \
+        int32_t status = (call);                                                 \
+        if (status != TOKENIZER_OK) {                                            \
+            fprintf(stderr, "%s failed with %d: %s\n", #call, status,            \
+                    tokenizer_last_error());                                     \
+            return 1;                                                            \
+        }                                                                        \
+    } while (0)
+
+int main(int argc, char** argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <vocab_file>\n", argv[0]);
+        return 1;
+    }
+
+    TokenizerVocab* vocab = NULL;
+    CHECK(tokenizer_vocab_load(argv[1], &vocab));
+
+    size_t size = 0;
+    CHECK(tokenizer_vocab_size(vocab, &size));
+    printf("vocab size %zu\n", size);
+
+    const char* text = "Hello, world!";
+    uint32_t* ids = NULL;
+    size_t ids_len = 0;
+    CHECK(tokenizer_encode(vocab, text, strlen(text), &ids, &ids_len));
+    printf("ids");
+    for (size_t i = 0; i < ids_len; i++) {
+        printf(" %u", ids[i]);
+    }
+    printf("\n");
+
+    char* decoded = NULL;
+    size_t decoded_len = 0;
+    CHECK(tokenizer_decode(vocab, ids, ids_len, &decoded, &decoded_len));
+    printf("decoded %s\n", decoded);
+    int same = decoded_len == strlen(text) && strcmp(decoded, text) == 0;
+
+    tokenizer_string_free(decoded, decoded_len);
+    tokenizer_ids_free(ids, ids_len);
+    tokenizer_vocab_free(vocab);
+
+    if (!same) {
+        fprintf(stderr, "decoded text differs\n");
+        return 1;
+    }
+
+    TokenizerVocab* missing = NULL;
+    if (tokenizer_vocab_load("/nonexistent/vocab.vcb", &missing) != TOKENIZER_IO_ERROR || missing != NULL) {
+        fprintf(stderr, "loading missing file should fail\n");
+        return 1;
+    }
+    printf("error %s\n", tokenizer_last_error());
+
+    return 0;
+}
@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[test]
+fn header_is_up_to_date() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/tokenizer.h"));
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/tokenizer.h");
+    assert!(
+        std::fs::read_to_string(&path).unwrap() == generated,
+        "{} is outdated, copy it from {}/tokenizer.h",
+        path.display(),
+        env!("OUT_DIR"),
+    );
+}
+
+/// Compiles `tests/c/test.c` against the built shared library and runs it
+#[cfg(target_os = "linux")]
+#[test]
+fn c_program() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // Test binary is placed in `target/<profile>/deps` next to the library
+    let deps_dir: PathBuf = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    assert!(deps_dir.join("libtokenizer_capi.so").exists(), "libtokenizer_capi.so isn't built");
+
+    let out_dir = std::env::temp_dir().join(format!("tokenizer_capi_{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let program = out_dir.join("test");
+
+    let status = Command::new("cc")
+        .arg(manifest_dir.join("tests/c/test.c"))
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&deps_dir)
+        .arg("-ltokenizer_capi")
+        .arg(format!("-Wl,-rpath,{}", deps_dir.display()))
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("C compiler `cc` is required");
+    assert!(status.success(), "failed to compile test.c");
+
+    let output = Command::new(&program)
+        .arg(manifest_dir.join("../content/vocab.vcb"))
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&out_dir).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("vocab size 12670\n"), "{stdout}");
+    assert!(stdout.contains("decoded Hello, world!\n"), "{stdout}");
+    assert!(stdout.contains("error /nonexistent/vocab.vcb: "), "{stdout}");
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use tokenizer::{parents, tokenizers::TokenId, TokenizerPipeline, Vocabulary};
+
+/// Maximum count of tokens printed by search
+const SEARCH_LIMIT: usize = 30;
+
+/// Background colors of token boundaries
+const COLORS: [&str; 6] = ["\x1b[30;41m", "\x1b[30;42m", "\x1b[30;43m", "\x1b[30;44m", "\x1b[30;45m", "\x1b[30;46m"];
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+
+const HELP: &str = "Commands:
+  /search <text>   tokens containing text
+  /prefix <text>   tokens starting with text
+  /id <id>         token with id, its parents and children
+  /token <text>    token with value, its parents and children
+  /help            this help
+  /quit            exit
+Any other line is tokenized.";
+
+/// Interactive vocabulary browser reading commands from stdin
+pub struct Browser<'a> {
+    pipeline: &'a TokenizerPipeline,
+    vocab: &'a Vocabulary,
+
+    /// Tokens merged from given token, sorted by id
+    children: HashMap<TokenId, Vec<TokenId>>,
+}
+
+impl<'a> Browser<'a> {
+    pub fn new(pipeline: &'a TokenizerPipeline) -> Self {
+        let vocab = pipeline.vocab();
+        let mut children: HashMap<TokenId, Vec<TokenId>> = HashMap::new();
+        for (id, _) in vocab.iter() {
+            if let Some((left, right)) = parents(id, vocab) {
+                children.entry(left).or_default().push(id);
+                if right != left {
+                    children.entry(right).or_default().push(id);
+                }
+            }
+        }
+
+        Self {
+            pipeline,
+            vocab,
+            children,
+        }
+    }
+
+    /// Runs the loop until `/quit` or end of input
+    pub fn run(&self, input: impl BufRead, output: &mut impl Write) -> Result<(), std::io::Error> {
+        writeln!(output, "{} tokens, type /help for commands", self.vocab.len())?;
+        write!(output, "> ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let (command, argument) = match line.strip_prefix('/') {
+                Some(x) => x.split_once(' ').unwrap_or((x, "")),
+                None => ("", line.as_str()),
+            };
+
+            match command {
+                "" => self.tokenize(argument, output)?,
+                "search" | "s" => self.search(|x| x.contains(argument), output)?,
+                "prefix" | "p" => self.search(|x| x.starts_with(argument), output)?,
+                "id" | "i" => match argument.trim().parse() {
+                    Ok(id) => self.show(id, output)?,
+                    Err(_) => writeln!(output, "invalid id {argument:?}")?,
+                },
+                "token" | "t" => match self.vocab.get_token_id(argument) {
+                    Some(id) => self.show(id, output)?,
+                    None => writeln!(output, "no token {argument:?}")?,
+                },
+                "help" | "h" => writeln!(output, "{HELP}")?,
+                "quit" | "q" => return Ok(()),
+                x => writeln!(output, "unknown command /{x}, type /help for commands")?,
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    fn tokenize(&self, text: &str, output: &mut impl Write) -> Result<(), std::io::Error> {
+        let tokens = self.pipeline.tokenize_with_offsets(text);
+        for (i, (_, range)) in tokens.iter().enumerate() {
+            write!(output, "{}{}", COLORS[i % COLORS.len()], &text[range.clone()])?;
+        }
+        writeln!(output, "{RESET}")?;
+
+        let ids: Vec<String> = tokens.iter().map(|x| x.0.to_string()).collect();
+        writeln!(output, "{DIM}{} tokens: {}{RESET}", tokens.len(), ids.join(" "))
+    }
+
+    fn search(&self, filter: impl Fn(&str) -> bool, output: &mut impl Write) -> Result<(), std::io::Error> {
+        let found: Vec<(TokenId, &str)> = self.vocab.iter().filter(|x| filter(x.1)).collect();
+        for (id, token) in found.iter().take(SEARCH_LIMIT) {
+            writeln!(output, "{id:>8} {token:?}")?;
+        }
+        if found.len() > SEARCH_LIMIT {
+            writeln!(output, "{DIM}... {} more{RESET}", found.len() - SEARCH_LIMIT)?;
+        }
+        writeln!(output, "{DIM}{} found{RESET}", found.len())
+    }
+
+    fn show(&self, id: TokenId, output: &mut impl Write) -> Result<(), std::io::Error> {
+        let token = match self.vocab.get_token(id) {
+            Some(x) => x,
+            None => return writeln!(output, "no token with id {id}"),
+        };
+
+        write!(output, "{BOLD}{token:?}{RESET} ({id})")?;
+        match self.vocab.get_merge(id) {
+            Some((rank, merge)) => writeln!(output, " merge #{rank}, count {}", merge.count)?,
+            None => writeln!(output)?,
+        }
+
+        if let Some((left, right)) = parents(id, self.vocab) {
+            writeln!(output, "  parents: {}  {}", self.format_token(left), self.format_token(right))?;
+        }
+        if let Some(children) = self.children.get(&id) {
+            let shown: Vec<String> = children.iter().take(SEARCH_LIMIT).map(|x| self.format_token(*x)).collect();
+            writeln!(output, "  children ({}): {}", children.len(), shown.join("  "))?;
+        }
+        Ok(())
+    }
+
+    fn format_token(&self, id: TokenId) -> String {
+        format!("{:?} ({id})", self.vocab.get_token(id).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tokenizer::{tokenizers::BpeTokenizer, Merge, VocabularyBuilder};
+
+    use super::*;
+
+    #[test]
+    fn run() {
+        let mut vocab = VocabularyBuilder::new()
+            .add_token("a".to_string(), 0)
+            .add_token("b".to_string(), 1)
+            .add_token("ab".to_string(), 2)
+            .add_token("ba".to_string(), 3)
+            .build();
+        vocab.add_merge(Merge { left: 0, right: 1, token: 2, count: 4 });
+        let pipeline = TokenizerPipeline::new(Box::new(BpeTokenizer::new()), vocab);
+        let browser = Browser::new(&pipeline);
+
+        let script = "/prefix a\n/id 2\n/token ba\n/id x\n/lol\nabb\n/quit\n/help\n";
+        let mut output = Vec::new();
+        browser.run(Cursor::new(script), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let expected = [
+            "4 tokens, type /help for commands".to_string(),
+            format!("> {:>8} \"a\"", 0),
+            format!("{:>8} \"ab\"", 2),
+            format!("{DIM}2 found{RESET}"),
+            format!("> {BOLD}\"ab\"{RESET} (2) merge #0, count 4"),
+            "  parents: \"a\" (0)  \"b\" (1)".to_string(),
+            format!("> {BOLD}\"ba\"{RESET} (3)"),
+            "  parents: \"b\" (1)  \"a\" (0)".to_string(),
+            "> invalid id \"x\"".to_string(),
+            "> unknown command /lol, type /help for commands".to_string(),
+            format!("> {}ab{}b{RESET}", COLORS[0], COLORS[1]),
+            format!("{DIM}2 tokens: 2 1{RESET}"),
+            "> ".to_string(),
+        ];
+        // Commands after /quit are ignored
+        assert_eq!(output, expected.join("\n"));
+    }
+}
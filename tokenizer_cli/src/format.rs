@@ -0,0 +1,195 @@
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::str::FromStr;
+
+use tokenizer::{tokenizers::TokenId, Vocabulary};
+
+/// Output format of `encode` and input format of `decode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Space separated ids, one line per input
+    Ids,
+    /// Quoted token values, one line per input
+    Pieces,
+    /// JSON object with ids, tokens and byte offsets, one line per input
+    Jsonl,
+    /// Little-endian `u16` ids of all inputs
+    U16,
+    /// Little-endian `u32` ids of all inputs
+    U32,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ids" => Ok(Format::Ids),
+            "pieces" => Ok(Format::Pieces),
+            "jsonl" => Ok(Format::Jsonl),
+            "u16" => Ok(Format::U16),
+            "u32" => Ok(Format::U32),
+            x => Err(format!("unknown format {x}, expected ids, pieces, jsonl, u16 or u32")),
+        }
+    }
+}
+
+/// Writes tokens of one input in given format
+pub fn write_tokens(
+    output: &mut impl Write,
+    format: Format,
+    source: &str,
+    tokens: &[(TokenId, Range<usize>)],
+    vocab: &Vocabulary,
+) -> Result<(), std::io::Error> {
+    match format {
+        Format::Ids => {
+            let ids: Vec<String> = tokens.iter().map(|x| x.0.to_string()).collect();
+            writeln!(output, "{}", ids.join(" "))
+        },
+        Format::Pieces => {
+            let pieces: Vec<String> = tokens.iter()
+                .map(|x| format!("{:?}", vocab.get_token(x.0).unwrap_or_default()))
+                .collect();
+            writeln!(output, "{}", pieces.join(" "))
+        },
+        Format::Jsonl => {
+            let ids: Vec<String> = tokens.iter().map(|x| x.0.to_string()).collect();
+            let pieces: Vec<String> = tokens.iter()
+                .map(|x| json_string(vocab.get_token(x.0).unwrap_or_default()))
+                .collect();
+            let offsets: Vec<String> = tokens.iter().map(|x| format!("[{},{}]", x.1.start, x.1.end)).collect();
+            writeln!(
+                output,
+                "{{\"source\":{},\"ids\":[{}],\"tokens\":[{}],\"offsets\":[{}]}}",
+                json_string(source), ids.join(","), pieces.join(","), offsets.join(","),
+            )
+        },
+        Format::U16 => {
+            let mut bytes = Vec::with_capacity(tokens.len() * 2);
+            for (id, _) in tokens {
+                let id = u16::try_from(*id).map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("token {id} doesn't fit into u16"))
+                })?;
+                bytes.extend(id.to_le_bytes());
+            }
+            output.write_all(&bytes)
+        },
+        Format::U32 => {
+            let bytes: Vec<u8> = tokens.iter().flat_map(|x| x.0.to_le_bytes()).collect();
+            output.write_all(&bytes)
+        },
+    }
+}
+
+/// Reads all ids from input in given format
+pub fn read_tokens(input: &mut impl Read, format: Format) -> Result<Vec<TokenId>, std::io::Error> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    match format {
+        Format::Ids => {
+            let text = String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))?;
+            text.split_whitespace()
+                .map(|x| x.parse().map_err(|_| invalid(format!("invalid token id {x}"))))
+                .collect()
+        },
+        Format::U16 => {
+            if bytes.len() % 2 != 0 {
+                return Err(invalid("u16 stream has odd length".to_string()));
+            }
+            Ok(bytes.chunks_exact(2).map(|x| u16::from_le_bytes([x[0], x[1]]) as TokenId).collect())
+        },
+        Format::U32 => {
+            if bytes.len() % 4 != 0 {
+                return Err(invalid("u32 stream length is not a multiple of 4".to_string()));
+            }
+            Ok(bytes.chunks_exact(4).map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]])).collect())
+        },
+        Format::Pieces | Format::Jsonl => Err(invalid("decode accepts ids, u16 or u32 formats".to_string())),
+    }
+}
+
+/// Returns JSON string literal
+pub fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            x if (x as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", x as u32)),
+            x => result.push(x),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizer::VocabularyBuilder;
+
+    use super::*;
+
+    fn vocab() -> Vocabulary {
+        VocabularyBuilder::new()
+            .add_token("a".to_string(), 0)
+            .add_token("b\"\n".to_string(), 1)
+            .add_token("c".to_string(), 70000)
+            .build()
+    }
+
+    fn write(format: Format, tokens: &[(TokenId, Range<usize>)]) -> Result<Vec<u8>, std::io::Error> {
+        let mut output = Vec::new();
+        write_tokens(&mut output, format, "in.txt", tokens, &vocab())?;
+        Ok(output)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let tokens = [(0, 0..1), (1, 1..4), (0, 4..5)];
+        for format in [Format::Ids, Format::U16, Format::U32] {
+            let bytes = write(format, &tokens).unwrap();
+            assert_eq!(read_tokens(&mut bytes.as_slice(), format).unwrap(), vec![0, 1, 0], "{format:?}");
+        }
+        assert_eq!(write(Format::Ids, &tokens).unwrap(), b"0 1 0\n");
+        assert_eq!(write(Format::U16, &tokens).unwrap(), [0, 0, 1, 0, 0, 0]);
+
+        let wide = [(70000, 0..1)];
+        let bytes = write(Format::U32, &wide).unwrap();
+        assert_eq!(read_tokens(&mut bytes.as_slice(), Format::U32).unwrap(), vec![70000]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = write(Format::U16, &[(0, 0..1), (70000, 1..2)]).unwrap_err();
+        assert_eq!(error.to_string(), "token 70000 doesn't fit into u16");
+
+        let error = read_tokens(&mut [1, 0, 2].as_slice(), Format::U16).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(read_tokens(&mut [1, 0, 0].as_slice(), Format::U32).is_err());
+        assert!(read_tokens(&mut "1 x".as_bytes(), Format::Ids).is_err());
+        for format in [Format::Pieces, Format::Jsonl] {
+            assert!(read_tokens(&mut "0 1".as_bytes(), format).is_err(), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(json_string("a\"b\\c"), r#""a\"b\\c""#);
+        assert_eq!(json_string("\n\r\t\u{1}\u{1f} é"), r#""\n\r\t\u0001\u001f é""#);
+
+        let output = write(Format::Jsonl, &[(0, 0..1), (1, 1..4)]).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"source\":\"in.txt\",\"ids\":[0,1],\"tokens\":[\"a\",\"b\\\"\\n\"],\"offsets\":[[0,1],[1,4]]}\n",
+        );
+        let pieces = write(Format::Pieces, &[(0, 0..1), (1, 1..4)]).unwrap();
+        assert_eq!(String::from_utf8(pieces).unwrap(), "\"a\" \"b\\\"\\n\"\n");
+    }
+}
@@ -1,107 +1,453 @@
-use std::{path::PathBuf, str::FromStr};
+mod browse;
+mod format;
+mod render;
 
-use tokenizer::*;
+use std::{error::Error, io::{Read, Write}, path::{Path, PathBuf}, str::FromStr};
 
-fn print_usage() {
-    let program = std::env::args().next().unwrap();
-    println!("usage: {program} <subprogram>");
-    println!("Subprograms: ");
-    println!("  fill <vocab_file> <corpus_file>");
-    println!("  tokenize <vocab_file> <text>");
+use cli_args::{Arg, Command, Matches, ParseError};
+use dataset::{DType, DatasetWriter};
+use format::Format;
+use tokenizer::{*, tokenizers::TokenId};
+
+fn cli() -> Command {
+    Command::new("tokenizer_cli")
+        .about("Trains and inspects BPE vocabularies")
+        .subcommand(Command::new("fill")
+            .about("Trains vocabulary on corpora")
+            .arg(Arg::positional("vocab_file").help("Output vocabulary file"))
+            .arg(Arg::positional("corpus").multiple().help(
+                "File or directory of .txt files, optionally followed by sampling weight: content/vm.txt=0.5",
+            ))
+            .arg(Arg::option("vocab-size").short('s').value_name("n").default("50000").help("Maximum vocabulary size"))
+            .arg(Arg::option("min-frequency").short('m').value_name("n").default("2").help("Minimal frequency of a pair to merge it, occurrences are multiplied by corpus weights"))
+            .arg(Arg::option("normalizer").short('n').value_name("name").default("none").help("none, lowercase, line-endings or comma separated list"))
+            .arg(Arg::option("pre-tokenizer").short('p').value_name("name").default("none").help("none, whitespace or words"))
+            .arg(Arg::option("alphabet").short('a').value_name("name").default("chars").help("Units of base tokens: chars or graphemes"))
+            .arg(Arg::option("temperature").short('t').value_name("t").help("Evens out shares of big and small corpora"))
+            .arg(Arg::option("post-processor").value_name("name").default("none").help("none or \"wrap <start> <end>\" adding special tokens around text"))
+            .arg(Arg::option("decoder").value_name("name").default("concat").help("concat or skip-special")))
+        .subcommand(Command::new("tokenize")
+            .about("Prints token ids of text")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("text").multiple()))
+        .subcommand(Command::new("encode")
+            .about("Encodes files or stdin")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("input").multiple().optional().help("Input files, reads stdin if not given"))
+            .arg(Arg::option("format").short('f').default("ids").help("ids, pieces, jsonl, u16 or u32"))
+            .arg(Arg::option("output").short('o').value_name("file").help("Output file, writes stdout if not given"))
+            .arg(Arg::option("cache").value_name("n").default("100000").help("Capacity of per-word encoding cache, used with a pre-tokenizer, 0 disables it")))
+        .subcommand(Command::new("decode")
+            .about("Decodes token ids to text")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("id").multiple().optional().help("Token ids, reads --input or stdin if not given"))
+            .arg(Arg::option("format").short('f').default("ids").help("ids, u16 or u32"))
+            .arg(Arg::option("input").short('i').value_name("file").help("Input file")))
+        .subcommand(Command::new("dataset")
+            .about("Builds pre-tokenized dataset file from corpora")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("output_file"))
+            .arg(Arg::positional("corpus").multiple().help("File or directory of .txt files, each file is a document"))
+            .arg(Arg::option("dtype").short('d').value_name("type").help("u16 or u32, smallest type fitting the vocabulary if not given"))
+            .arg(Arg::flag("no-index").help("Don't write document boundaries"))
+            .arg(Arg::option("cache").value_name("n").default("100000").help("Capacity of per-word encoding cache, used with a pre-tokenizer, 0 disables it")))
+        .subcommand(Command::new("stats")
+            .about("Reports tokenization quality on corpora")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("corpus").multiple().help("File or directory of .txt files")))
+        .subcommand(Command::new("render")
+            .about("Renders tokens of a file and vocabulary table as HTML page")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("file"))
+            .arg(Arg::option("output").short('o').value_name("file").help("Output file, writes stdout if not given")))
+        .subcommand(Command::new("explain")
+            .about("Shows merges which produced every token of text")
+            .arg(Arg::positional("vocab_file"))
+            .arg(Arg::positional("text").multiple())
+            .arg(Arg::flag("dot").help("Print merge trees as Graphviz graph")))
+        .subcommand(Command::new("browse")
+            .about("Interactive vocabulary browser")
+            .arg(Arg::positional("vocab_file")))
+        .subcommand(Command::new("vocab-diff")
+            .about("Compares two vocabularies")
+            .arg(Arg::positional("vocab_a"))
+            .arg(Arg::positional("vocab_b")))
+        .subcommand(Command::new("vocab-merge")
+            .about("Merges two vocabularies, keeps ids of vocab_a and appends new tokens of vocab_b")
+            .arg(Arg::positional("vocab_a"))
+            .arg(Arg::positional("vocab_b"))
+            .arg(Arg::positional("output_file")))
+}
+
+/// Corpus path with optional sampling weight: `path` or `path=weight`,
+/// paths containing `=` are kept whole unless they end with a number
+#[derive(Debug, PartialEq)]
+struct CorpusArg {
+    path: PathBuf,
+    weight: f64,
+}
+
+impl FromStr for CorpusArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((path, weight)) = s.rsplit_once('=') else {
+            return Ok(Self { path: PathBuf::from(s), weight: 1.0 });
+        };
+        match weight.parse::<f64>() {
+            Ok(weight) if weight >= 0.0 && weight.is_finite() => Ok(Self { path: PathBuf::from(path), weight }),
+            Ok(_) => Err(format!("invalid corpus weight {weight}")),
+            Err(_) => Ok(Self { path: PathBuf::from(s), weight: 1.0 }),
+        }
+    }
 }
 
-fn fill_subcommand(vocab_path: PathBuf, corpus_path: PathBuf) -> Result<(), std::io::Error> {
+fn fill_subcommand(
+    vocab_path: PathBuf,
+    corpora: Vec<CorpusArg>,
+    temperature: Option<f64>,
+    tokenizer: BpeTokenizer,
+    post_processor: PostProcessor,
+    decoder: Decoder,
+) -> Result<(), std::io::Error> {
     let mut vocab: Vocabulary = VocabularyBuilder::new()
         .add_token("[UNK]".to_string(), 0)
         .build();
+    for token in post_processor.special_tokens() {
+        vocab.try_add_token(token.to_string());
+    }
 
-    let corpus = std::fs::read_to_string(corpus_path.clone())?;
+    let mut sources = Vec::new();
+    for CorpusArg { path, weight } in corpora.iter() {
+        let source = CorpusSource::from_path(path, *weight)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        sources.push(source);
+    }
+    if let Some(temperature) = temperature {
+        apply_temperature(&mut sources, temperature);
+    }
+    for (corpus, source) in corpora.iter().zip(sources.iter()) {
+        println!("{}: {} chars, weight {:.3}", corpus.path.display(), source.len(), source.weight);
+    }
 
-    let mut tokenizer = tokenizers::BpeTokenizer::new();
-    tokenizer.max_size = 50000;
-    tokenizer.fill_vocab(&corpus, &mut vocab);
-    std::fs::write(vocab_path, vocab.serialize())?;
+    tokenizer.fill_vocab_weighted(&sources, &mut vocab);
+    let pipeline = TokenizerPipeline::new(Box::new(tokenizer), vocab)
+        .with_post_processor(post_processor)
+        .with_decoder(decoder);
+    std::fs::write(vocab_path, pipeline.serialize())?;
 
     Ok(())
 }
 
 fn tokenize_subcommand(vocab_path: PathBuf, text: &str) -> Result<String, std::io::Error> {
-    let vocab = Vocabulary::from_str(&std::fs::read_to_string(vocab_path)?);
-    let tokenizer = tokenizers::BpeTokenizer::new();
-    let tokens = tokenizer.tokenize(text, &vocab);
+    let pipeline = TokenizerPipeline::load(&vocab_path)?;
+    let tokens = pipeline.tokenize(text);
     Ok(tokens.iter().map(|x| x.to_string()).collect::<Vec<_>>().as_slice().join(" "))
 }
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.len() < 2 {
-        print_usage();
-        return;
-    }
-
-    match args[1].as_str() {
-        "fill" => {
-            let vocab_path = if let Some(path) = args.get(2) {
-                if let Some(x) = PathBuf::from_str(path).ok() {
-                    x
-                } else {
-                    eprintln!("Can't read file {}", path);
-                    print_usage();
-                    return
-                }
-            } else {
-                eprintln!("No vocab file");
-                return
-            };
-
-            let corpus_path = if let Some(path) = args.get(3) {
-                if let Some(x) = PathBuf::from_str(path).ok() {
-                    x
-                } else {
-                    eprintln!("Can't read file {}", path);
-                    print_usage();
-                    return
-                }
-            } else {
-                eprintln!("No corpus file");
-                return
-            };
-
-            if let Err(e) = fill_subcommand(vocab_path, corpus_path) {
-                eprintln!("{e}");
+/// Loads pipeline with encoding cache, without a pre-tokenizer pieces are whole files
+/// which are too long to be cached, so the cache is left out
+fn load_with_cache(vocab_path: &Path, cache: usize) -> Result<TokenizerPipeline, std::io::Error> {
+    let pipeline = TokenizerPipeline::load(vocab_path)?;
+    Ok(match pipeline.vocab().pre_tokenizer() {
+        PreTokenizer::None => pipeline,
+        _ => pipeline.with_cache(cache),
+    })
+}
+
+fn encode_subcommand(
+    vocab_path: PathBuf,
+    inputs: Vec<PathBuf>,
+    format: Format,
+    output: Option<PathBuf>,
+    cache: usize,
+) -> Result<(), std::io::Error> {
+    let pipeline = load_with_cache(&vocab_path, cache)?;
+
+    let mut output: Box<dyn Write> = match output {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut output = std::io::BufWriter::new(&mut output);
+
+    if inputs.is_empty() {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        let tokens = pipeline.tokenize_with_offsets(&text);
+        format::write_tokens(&mut output, format, "-", &tokens, pipeline.vocab())?;
+    }
+    for path in inputs {
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        let tokens = pipeline.tokenize_with_offsets(&text);
+        format::write_tokens(&mut output, format, &path.to_string_lossy(), &tokens, pipeline.vocab())?;
+    }
+
+    output.flush()
+}
+
+fn decode_subcommand(vocab_path: PathBuf, ids: Vec<TokenId>, format: Format, input: Option<PathBuf>) -> Result<(), std::io::Error> {
+    let pipeline = TokenizerPipeline::load(&vocab_path)?;
+
+    let ids = match input {
+        Some(path) => format::read_tokens(&mut std::fs::File::open(path)?, format)?,
+        None if ids.is_empty() => format::read_tokens(&mut std::io::stdin().lock(), format)?,
+        None => ids,
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(pipeline.decode(&ids).as_bytes())?;
+    stdout.flush()
+}
+
+fn dataset_subcommand(
+    vocab_path: PathBuf,
+    output_path: PathBuf,
+    corpus_paths: Vec<PathBuf>,
+    dtype: Option<DType>,
+    has_index: bool,
+    cache: usize,
+) -> Result<(), Box<dyn Error>> {
+    let pipeline = load_with_cache(&vocab_path, cache)?;
+    let vocab = pipeline.vocab();
+    let dtype = dtype.unwrap_or_else(|| DType::for_max_id(vocab.iter().map(|x| x.0).max().unwrap_or(0)));
+
+    let output = std::io::BufWriter::new(std::fs::File::create(&output_path)?);
+    let mut writer = DatasetWriter::new(output, dtype, vocab.fingerprint())?.with_index(has_index);
+    for path in corpus_paths.iter() {
+        let source = CorpusSource::from_path(path, 1.0)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        for document in source.documents.iter() {
+            writer.push_document(&pipeline.tokenize(document))?;
+        }
+    }
+
+    let header = *writer.header();
+    writer.finish()?;
+    println!(
+        "{}: {} tokens, {} documents, {}, vocabulary {}",
+        output_path.display(), header.tokens, header.documents, header.dtype, header.fingerprint,
+    );
+    if let Some(stats) = pipeline.cache_stats().filter(|x| x.hits + x.misses > 0) {
+        println!("Cache hit rate {:.1}%, {} pieces cached", stats.hit_rate() * 100.0, stats.len);
+    }
+
+    Ok(())
+}
+
+fn stats_subcommand(vocab_path: PathBuf, corpus_paths: Vec<PathBuf>) -> Result<(), std::io::Error> {
+    let pipeline = TokenizerPipeline::load(&vocab_path)?;
+    let vocab = pipeline.vocab();
+
+    let mut stats = TokenizationStats::new();
+    for path in corpus_paths.iter() {
+        let source = CorpusSource::from_path(path, 1.0)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        for document in source.documents.iter() {
+            // Special tokens of post-processor aren't part of the text
+            let tokens = pipeline.model().tokenize(document, vocab);
+            stats.add(&vocab.normalizer().normalize(document), &tokens, vocab);
+        }
+    }
+
+    let unused = stats.unused_tokens(vocab).len();
+    println!(
+        "Vocabulary: {} tokens, {} used ({:.1}%), {} never used",
+        vocab.len(), vocab.len() - unused, stats.utilization(vocab) * 100.0, unused,
+    );
+    println!();
+
+    println!("{:<10} {:>10} {:>10} {:>10} {:>12} {:>12} {:>9}", "", "chars", "tokens", "words", "chars/token", "tokens/word", "unk rate");
+    let rows = std::iter::once(("Total".to_string(), &stats.total))
+        .chain(stats.by_script.iter().map(|(script, x)| (format!("{script:?}"), x)));
+    for (name, x) in rows {
+        println!(
+            "{:<10} {:>10} {:>10} {:>10} {:>12.3} {:>12.3} {:>8.3}%",
+            name, x.chars, x.tokens, x.words, x.chars_per_token(), x.fertility(), x.unk_rate() * 100.0,
+        );
+    }
+    println!();
+
+    println!("Token length histogram:");
+    let max = stats.length_histogram.values().max().cloned().unwrap_or(1);
+    for (len, count) in stats.length_histogram.iter() {
+        println!("{:>4} {:>10} {}", len, count, "#".repeat(count * 50 / max));
+    }
+
+    Ok(())
+}
+
+fn render_subcommand(vocab_path: PathBuf, path: PathBuf, output: Option<PathBuf>) -> Result<(), std::io::Error> {
+    let pipeline = TokenizerPipeline::load(&vocab_path)?;
+    let text = std::fs::read_to_string(&path)?;
+    let tokens = pipeline.tokenize_with_offsets(&text);
+
+    let html = render::render_html(&path.to_string_lossy(), &text, &tokens, pipeline.vocab());
+    match output {
+        Some(output) => std::fs::write(output, html),
+        None => std::io::stdout().lock().write_all(html.as_bytes()),
+    }
+}
+
+fn explain_subcommand(vocab_path: PathBuf, text: &str, dot: bool) -> Result<(), std::io::Error> {
+    let pipeline = TokenizerPipeline::load(&vocab_path)?;
+    let trees = explain(pipeline.model(), text, pipeline.vocab());
+
+    if dot {
+        print!("{}", to_dot(&trees));
+    } else {
+        for tree in trees {
+            println!("{tree}");
+        }
+    }
+
+    Ok(())
+}
+
+fn browse_subcommand(vocab_path: PathBuf) -> Result<(), std::io::Error> {
+    let pipeline = TokenizerPipeline::load(&vocab_path)?;
+    browse::Browser::new(&pipeline).run(std::io::stdin().lock(), &mut std::io::stdout().lock())
+}
+
+fn vocab_diff_subcommand(a_path: PathBuf, b_path: PathBuf) -> Result<(), std::io::Error> {
+    let a = TokenizerPipeline::load(&a_path)?;
+    let b = TokenizerPipeline::load(&b_path)?;
+    let diff = a.vocab().diff(b.vocab());
+
+    println!("Only in A ({}):", diff.only_a.len());
+    for (id, token) in diff.only_a.iter() {
+        println!("  {id} = {token:?}");
+    }
+    println!("Only in B ({}):", diff.only_b.len());
+    for (id, token) in diff.only_b.iter() {
+        println!("  {id} = {token:?}");
+    }
+    println!("Different ids ({}):", diff.different_ids.len());
+    for (token, a_id, b_id) in diff.different_ids.iter() {
+        println!("  {token:?}: {a_id} -> {b_id}");
+    }
+    println!("Same: {}", diff.same);
+
+    Ok(())
+}
+
+fn vocab_merge_subcommand(a_path: PathBuf, b_path: PathBuf, output_path: PathBuf) -> Result<(), std::io::Error> {
+    let a = TokenizerPipeline::load(&a_path)?;
+    let b = TokenizerPipeline::load(&b_path)?;
+    let merged = a.vocab().merge(b.vocab());
+    println!("{} + {} -> {} tokens", a.vocab().len(), b.vocab().len(), merged.len());
+
+    // Merged vocabulary keeps pipeline settings of vocab_a
+    let model = model_by_name(a.model().name()).unwrap();
+    let merged = TokenizerPipeline::new(model, merged)
+        .with_post_processor(a.post_processor().clone())
+        .with_decoder(a.decoder());
+    std::fs::write(output_path, merged.serialize())
+}
+
+fn run(matches: &Matches) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        Some(("fill", args)) => {
+            let tokenizer = BpeTokenizer::builder()
+                .max_size(args.get("vocab-size")?)
+                .min_frequency(args.get("min-frequency")?)
+                .normalizer(args.get("normalizer")?)
+                .pre_tokenizer(args.get("pre-tokenizer")?)
+                .alphabet(args.get("alphabet")?)
+                .build();
+
+            let temperature: Option<f64> = args.get_opt("temperature")?;
+            if temperature.is_some_and(|x| x <= 0.0) {
+                return Err("temperature should be a positive number".into());
             }
+
+            fill_subcommand(
+                args.get("vocab_file")?,
+                args.get_many("corpus")?,
+                temperature,
+                tokenizer,
+                args.get("post-processor")?,
+                args.get("decoder")?,
+            )?;
         },
-        "tokenize" => {
-            let vocab_path = if let Some(path) = args.get(2) {
-                if let Some(x) = PathBuf::from_str(path).ok() {
-                    x
-                } else {
-                    eprintln!("Can't read file {}", path);
-                    print_usage();
-                    return
-                }
-            } else {
-                eprintln!("No vocab file");
-                return
-            };
-
-            if args.len() < 4 {
-                eprintln!("No text for tokenization");
-                print_usage();
-                return;
+        Some(("tokenize", args)) => {
+            let text = args.get_many::<String>("text")?.join(" ");
+            println!("{}", tokenize_subcommand(args.get("vocab_file")?, &text)?);
+        },
+        Some(("encode", args)) => {
+            encode_subcommand(
+                args.get("vocab_file")?,
+                args.get_many("input")?,
+                args.get("format")?,
+                args.get_opt("output")?,
+                args.get("cache")?,
+            )?;
+        },
+        Some(("decode", args)) => {
+            let ids: Vec<TokenId> = args.get_many("id")?;
+            let input: Option<PathBuf> = args.get_opt("input")?;
+            if !ids.is_empty() && input.is_some() {
+                return Err(args.error("token ids and --input can't be given together").into());
             }
-            let text = std::env::args().skip(3).collect::<Vec<_>>().join(" ");
-
-            match tokenize_subcommand(vocab_path, &text) {
-                Ok(tokens) => println!("{tokens}"),
-                Err(e) => eprintln!("{e}"),
-            } 
+            decode_subcommand(args.get("vocab_file")?, ids, args.get("format")?, input)?;
+        },
+        Some(("dataset", args)) => {
+            dataset_subcommand(
+                args.get("vocab_file")?,
+                args.get("output_file")?,
+                args.get_many("corpus")?,
+                args.get_opt("dtype")?,
+                !args.flag("no-index"),
+                args.get("cache")?,
+            )?;
+        },
+        Some(("stats", args)) => {
+            stats_subcommand(args.get("vocab_file")?, args.get_many("corpus")?)?;
+        },
+        Some(("render", args)) => {
+            render_subcommand(args.get("vocab_file")?, args.get("file")?, args.get_opt("output")?)?;
+        },
+        Some(("explain", args)) => {
+            let text = args.get_many::<String>("text")?.join(" ");
+            explain_subcommand(args.get("vocab_file")?, &text, args.flag("dot"))?;
+        },
+        Some(("browse", args)) => {
+            browse_subcommand(args.get("vocab_file")?)?;
+        },
+        Some(("vocab-diff", args)) => {
+            vocab_diff_subcommand(args.get("vocab_a")?, args.get("vocab_b")?)?;
         },
-        x => {
-            eprintln!("Unknown command {x}");
-            print_usage();
-            return;
+        Some(("vocab-merge", args)) => {
+            vocab_merge_subcommand(args.get("vocab_a")?, args.get("vocab_b")?, args.get("output_file")?)?;
+        },
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+fn main() {
+    let matches = cli().parse_env().unwrap_or_else(|e| e.exit());
+
+    if let Err(e) = run(&matches) {
+        if let Some(e) = e.downcast_ref::<ParseError>() {
+            e.exit();
         }
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corpus_arg() {
+        let corpus = |path: &str, weight| Ok(CorpusArg { path: PathBuf::from(path), weight });
+        assert_eq!("content/vm.txt".parse(), corpus("content/vm.txt", 1.0));
+        assert_eq!("content/vm.txt=0.5".parse(), corpus("content/vm.txt", 0.5));
+        assert_eq!("data/a=b.txt".parse(), corpus("data/a=b.txt", 1.0));
+        assert_eq!("data/a=b.txt=2".parse(), corpus("data/a=b.txt", 2.0));
+        assert!("content/vm.txt=-1".parse::<CorpusArg>().is_err());
     }
 }
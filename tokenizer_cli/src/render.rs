@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::Range;
+
+use tokenizer::{tokenizers::TokenId, Vocabulary};
+
+/// Background colors of token spans, neighbour tokens always differ
+const COLORS: [&str; 6] = ["#ffd6a5", "#caffbf", "#9bf6ff", "#bdb2ff", "#ffc6ff", "#fdffb6"];
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+#text { font-family: monospace; white-space: pre-wrap; line-height: 1.8; border: 1px solid #ccc; padding: 1em; }
+#text span { border-radius: 3px; }
+#text span:hover { outline: 2px solid #333; }
+table { border-collapse: collapse; font-family: monospace; }
+th { cursor: pointer; user-select: none; background: #eee; }
+th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }
+td.num { text-align: right; }
+#search { margin: 1em 0; padding: 4px; width: 20em; }
+";
+
+const SCRIPT: &str = "
+const rows = Array.from(document.querySelectorAll('#vocab tbody tr'));
+const tbody = document.querySelector('#vocab tbody');
+document.getElementById('search').addEventListener('input', e => {
+  const query = e.target.value.toLowerCase();
+  for (const row of rows) {
+    row.style.display = row.cells[1].textContent.toLowerCase().includes(query) ? '' : 'none';
+  }
+});
+let sorted = { column: 2, descending: true };
+document.querySelectorAll('#vocab th').forEach((th, column) => th.addEventListener('click', () => {
+  sorted = { column, descending: sorted.column === column ? !sorted.descending : column !== 1 };
+  const key = row => column === 1 ? row.cells[1].textContent : Number(row.cells[column].dataset.value);
+  rows.sort((a, b) => {
+    const x = key(a), y = key(b);
+    const order = x < y ? -1 : x > y ? 1 : 0;
+    return sorted.descending ? -order : order;
+  });
+  tbody.append(...rows);
+}));
+";
+
+/// Renders self-contained HTML page with color-coded tokens of text
+/// and a table of vocabulary with token frequencies in the text.
+///
+/// Every character of text is shown once: text of a token overlapping previous ones
+/// starts after them, so a token fully covered by previous ones has an empty span,
+/// and text not covered by any token is shown without span.
+pub fn render_html(title: &str, text: &str, tokens: &[(TokenId, Range<usize>)], vocab: &Vocabulary) -> String {
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, "<html><head><meta charset=\"utf-8\"><title>{}</title>", escape(title));
+    let _ = writeln!(html, "<style>{STYLE}</style></head><body>");
+    let _ = writeln!(
+        html,
+        "<h1>{}</h1><p>{} chars, {} tokens, vocabulary of {} tokens</p>",
+        escape(title), text.chars().count(), tokens.len(), vocab.len(),
+    );
+
+    html.push_str("<div id=\"text\">");
+    let mut position = 0;
+    for (i, (token, range)) in tokens.iter().enumerate() {
+        // Text removed by normalizer isn't covered by any token
+        if range.start > position {
+            html.push_str(&escape(&text[position..range.start]));
+        }
+        let start = range.start.max(position);
+        let end = range.end.max(start);
+        let _ = write!(
+            html,
+            "<span style=\"background:{}\" title=\"{}\">{}</span>",
+            COLORS[i % COLORS.len()],
+            escape(&describe(*token, vocab)),
+            escape(&text[start..end]),
+        );
+        position = end;
+    }
+    html.push_str(&escape(&text[position..]));
+    html.push_str("</div>\n");
+
+    let mut counts: HashMap<TokenId, usize> = HashMap::new();
+    for (token, _) in tokens {
+        *counts.entry(*token).or_default() += 1;
+    }
+    let mut rows: Vec<(TokenId, &str, usize)> = vocab.iter()
+        .map(|(id, token)| (id, token, counts.get(&id).cloned().unwrap_or(0)))
+        .collect();
+    rows.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+
+    html.push_str("<h2>Vocabulary</h2>\n<input id=\"search\" placeholder=\"Search tokens\">\n");
+    html.push_str("<table id=\"vocab\"><thead><tr><th>id</th><th>token</th><th>count</th><th>merge</th></tr></thead><tbody>\n");
+    for (id, token, count) in rows {
+        let rank = vocab.get_merge(id).map(|x| x.0);
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"num\" data-value=\"{id}\">{id}</td><td>{}</td><td class=\"num\" data-value=\"{count}\">{count}</td><td class=\"num\" data-value=\"{}\">{}</td></tr>",
+            escape(&format!("{token:?}")),
+            rank.map_or(-1, |x| x as i64),
+            rank.map(|x| format!("#{x}")).unwrap_or_default(),
+        );
+    }
+    html.push_str("</tbody></table>\n");
+
+    let _ = writeln!(html, "<script>{SCRIPT}</script>");
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// Returns hover text of token
+fn describe(token: TokenId, vocab: &Vocabulary) -> String {
+    let value = vocab.get_token(token).unwrap_or_default();
+    match vocab.get_merge(token) {
+        Some((rank, merge)) => format!("{value:?}\nid {token}\nmerge #{rank}, count {}", merge.count),
+        None => format!("{value:?}\nid {token}"),
+    }
+}
+
+/// Escapes text for HTML content and attribute values
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            x => result.push(x),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizer::VocabularyBuilder;
+
+    use super::*;
+
+    /// Returns contents of token spans
+    fn spans(html: &str) -> Vec<&str> {
+        let text = html.split("<div id=\"text\">").nth(1).unwrap().split("</div>").next().unwrap();
+        text.split("</span>")
+            .filter_map(|x| x.split_once("<span ").map(|(_, span)| span.split_once('>').unwrap().1))
+            .collect()
+    }
+
+    #[test]
+    fn escape_text() {
+        assert_eq!(escape("<a href=\"x\">&'</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;");
+        assert_eq!(escape("мир"), "мир");
+    }
+
+    #[test]
+    fn render() {
+        let vocab = VocabularyBuilder::new()
+            .add_token("<".to_string(), 0)
+            .add_token("b>".to_string(), 1)
+            .add_token("&".to_string(), 2)
+            .build();
+        let text = "<b> & x";
+        let tokens = [(0, 0..1), (1, 1..3), (2, 4..5)];
+        let html = render_html("a < b", text, &tokens, &vocab);
+
+        assert!(html.contains("<title>a &lt; b</title>"));
+        assert_eq!(spans(&html), vec!["&lt;", "b&gt;", "&amp;"]);
+        let text = html.split("<div id=\"text\">").nth(1).unwrap();
+        assert!(text.contains("</span> <span"));
+        assert!(text.starts_with("<span style=\"background:#ffd6a5\" title=\"&quot;&lt;&quot;\nid 0\">"));
+        assert!(text.contains("</span> x</div>"));
+    }
+
+    #[test]
+    fn overlapping() {
+        let vocab = VocabularyBuilder::new().build();
+        let text = "abcdef";
+        // Second token overlaps the first one, third is covered by both, the last is empty
+        let tokens = [(0, 0..3), (1, 2..5), (2, 1..4), (3, 6..6)];
+        let html = render_html("", text, &tokens, &vocab);
+        assert_eq!(spans(&html), vec!["abc", "de", "", ""]);
+        assert!(html.contains("</span>f<span"));
+    }
+}